@@ -0,0 +1,89 @@
+use crate::{cdn, error::Location, raw, SpoolsError};
+use serde::{Deserialize, Serialize};
+
+/// Preview card for a link shared within a post
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct LinkPreview {
+    pub url: String,
+    pub display_url: String,
+    pub title: Option<String>,
+    pub image: Option<String>,
+}
+
+impl LinkPreview {
+    /// Whether the preview's image URL has expired
+    pub fn is_expired(&self) -> bool {
+        self.image.as_deref().is_some_and(cdn::is_expired)
+    }
+
+    /// Converts the response model for link preview attachments into the LinkPreview data type
+    pub(crate) fn from(object: &raw::LinkPreview, at: &Location) -> Result<Self, SpoolsError> {
+        let url = object.url.to_owned().ok_or_else(|| at.missing("/url"))?;
+
+        // Fall back to the full URL if Threads doesn't provide a shortened one
+        let display_url = object.display_url.to_owned().unwrap_or(url.to_owned());
+
+        let title = object.title.to_owned().filter(|title| !title.is_empty());
+
+        let image = object
+            .image_url
+            .to_owned()
+            .filter(|image| !image.is_empty());
+
+        Ok(LinkPreview {
+            url,
+            display_url,
+            title,
+            image,
+        })
+    }
+}
+
+/// Poll attached to a post
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Poll {
+    pub options: Vec<PollOption>,
+    pub total_votes: Option<u64>,
+    pub expires_at: Option<u64>,
+    pub closed: bool,
+}
+
+/// Option within a poll and its tally
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PollOption {
+    pub text: String,
+    pub votes: Option<u64>,
+    pub percentage: Option<f64>,
+}
+
+impl Poll {
+    /// Converts the response model for poll attachments into the Poll data type
+    pub(crate) fn from(object: &raw::Poll, at: &Location) -> Result<Self, SpoolsError> {
+        let options = object
+            .tallies
+            .as_ref()
+            .ok_or_else(|| at.missing("/tallies"))?
+            .iter()
+            .map(|tally| PollOption {
+                text: tally.text.to_owned().unwrap_or_default(),
+                votes: tally.count,
+                percentage: tally.vote_percentage,
+            })
+            .collect::<Vec<PollOption>>();
+
+        // Threads only sends the total when it's known, so try adding up the options otherwise
+        let total_votes = object.total_votes.or_else(|| {
+            options
+                .iter()
+                .map(|option| option.votes)
+                .sum::<Option<u64>>()
+        });
+
+        Ok(Poll {
+            options,
+            total_votes,
+            expires_at: object.expires_at,
+            closed: object.finished.unwrap_or(false),
+        })
+    }
+}
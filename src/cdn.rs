@@ -0,0 +1,32 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Get the expiry time out of a signed Instagram CDN URL, as a UNIX timestamp
+///
+/// Meta stores it in the `oe` query parameter, encoded in hexadecimal.
+pub(crate) fn expiry(url: &str) -> Option<u64> {
+    let (_, query) = url.split_once('?')?;
+    let query = query.split('#').next().unwrap_or_default();
+
+    query
+        .split('&')
+        .find_map(|param| param.strip_prefix("oe="))
+        .and_then(|value| u64::from_str_radix(value, 16).ok())
+}
+
+/// Whether a signed Instagram CDN URL has expired
+///
+/// URLs without an expiry time are assumed to stay valid.
+pub(crate) fn is_expired(url: &str) -> bool {
+    match expiry(url) {
+        Some(expiry) => expiry <= now(),
+        None => false,
+    }
+}
+
+/// Current UNIX timestamp
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_secs())
+        .unwrap_or(0)
+}
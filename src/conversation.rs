@@ -0,0 +1,98 @@
+use crate::post::Subpost;
+use serde::{Deserialize, Serialize};
+
+/// Conversation around a post, with the posts leading up to it and its replies as a tree
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Conversation {
+    /// Post which started the conversation, which is the requested post if it isn't a reply
+    ///
+    /// Threads doesn't always return the whole conversation, so this is left empty if the first
+    /// post it did return is a reply itself.
+    pub root: Option<Subpost>,
+    /// Posts from the root down to the one the requested post replies to
+    pub ancestors: Vec<Subpost>,
+    pub post: Subpost,
+    pub replies: Vec<Reply>,
+}
+
+/// Reply within a conversation, along with its own replies
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Reply {
+    pub post: Subpost,
+    pub replies: Vec<Reply>,
+}
+
+impl Conversation {
+    /// Build a conversation out of the reply chains returned for a post's thread
+    ///
+    /// Threads returns the chain leading up to the post first, followed by one chain per branch
+    /// of replies, where each post replies to the one before it. Chains repeating the start of
+    /// another are merged into the same branch. Replies deeper than `depth` are left out.
+    pub(crate) fn from_chains(chains: &[Vec<Subpost>], code: &str, depth: usize) -> Option<Self> {
+        let chain_index = chains
+            .iter()
+            .position(|chain| chain.iter().any(|post| post.code == code))?;
+        let chain = &chains[chain_index];
+        let post_index = chain.iter().position(|post| post.code == code)?;
+
+        let ancestors: Vec<Subpost> = chains[..chain_index]
+            .iter()
+            .flatten()
+            .chain(&chain[..post_index])
+            .cloned()
+            .collect();
+
+        let post = chain[post_index].to_owned();
+        let first = ancestors.first().unwrap_or(&post);
+        let root = (!first.is_reply).then(|| first.to_owned());
+
+        // The post's own chain may continue, such as when its author kept the thread going
+        let mut replies: Vec<Reply> = vec![];
+        Reply::insert(&mut replies, &chain[post_index + 1..], depth);
+
+        for branch in &chains[chain_index + 1..] {
+            Reply::insert(&mut replies, branch, depth);
+        }
+
+        Some(Conversation {
+            root,
+            ancestors,
+            post,
+            replies,
+        })
+    }
+}
+
+impl Reply {
+    /// Add a chain of replies to a list of branches, merging it into the branch it starts with
+    fn insert(branches: &mut Vec<Reply>, chain: &[Subpost], depth: usize) {
+        let Some(first) = chain.first() else {
+            return;
+        };
+
+        if depth == 0 {
+            return;
+        }
+
+        let index = match branches
+            .iter()
+            .position(|branch| branch.post.code == first.code)
+        {
+            Some(index) => index,
+            None => {
+                branches.push(Reply {
+                    post: first.to_owned(),
+                    replies: vec![],
+                });
+                branches.len() - 1
+            }
+        };
+
+        Reply::insert(&mut branches[index].replies, &chain[1..], depth - 1);
+    }
+
+    /// Amount of replies within this branch, not counting the reply itself
+    pub fn count(&self) -> usize {
+        self.replies.iter().map(|reply| reply.count() + 1).sum()
+    }
+}
@@ -0,0 +1,85 @@
+use crate::user::Author;
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
+
+/// Rich text entity within a post's body
+///
+/// The range is given in bytes, and always falls on character boundaries, so it can be used to
+/// slice the body directly.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Entity {
+    pub kind: EntityKind,
+    pub range: Range<usize>,
+}
+
+/// What an entity refers to
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub enum EntityKind {
+    Mention(Author),
+    Link(String),
+    Tag(String),
+}
+
+/// Section of a post's body, either plain or covered by an entity
+#[derive(Debug, Clone)]
+pub enum Segment<'a> {
+    Text(&'a str),
+    Entity(&'a Entity, &'a str),
+}
+
+/// Iterator over the segments of a post's body
+#[derive(Debug, Clone)]
+pub struct Segments<'a> {
+    body: &'a str,
+    entities: std::slice::Iter<'a, Entity>,
+    pending: Option<&'a Entity>,
+    cursor: usize,
+}
+
+impl<'a> Segments<'a> {
+    pub(crate) fn new(body: &'a str, entities: &'a [Entity]) -> Self {
+        Segments {
+            body,
+            entities: entities.iter(),
+            pending: None,
+            cursor: 0,
+        }
+    }
+}
+
+impl<'a> Iterator for Segments<'a> {
+    type Item = Segment<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // Skip entities which don't fit the body, or overlap with already emitted ones
+        let next = match self.pending.take() {
+            Some(entity) => Some(entity),
+            None => self.entities.by_ref().find(|entity| {
+                entity.range.start >= self.cursor && self.body.get(entity.range.clone()).is_some()
+            }),
+        };
+
+        match next {
+            // Emit the plain text leading to the next entity first
+            Some(entity) if entity.range.start > self.cursor => {
+                let text = &self.body[self.cursor..entity.range.start];
+                self.cursor = entity.range.start;
+                self.pending = Some(entity);
+
+                Some(Segment::Text(text))
+            }
+            Some(entity) => {
+                self.cursor = entity.range.end;
+
+                Some(Segment::Entity(entity, &self.body[entity.range.clone()]))
+            }
+            None if self.cursor < self.body.len() => {
+                let text = &self.body[self.cursor..];
+                self.cursor = self.body.len();
+
+                Some(Segment::Text(text))
+            }
+            None => None,
+        }
+    }
+}
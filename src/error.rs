@@ -1,27 +1,74 @@
 use core::fmt;
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use std::fmt::Write;
 use thiserror::Error;
 
 /// Error type for spools
+///
+/// Errors coming from a request carry the operation which failed, either the name of the GraphQL
+/// query or the URL being fetched.
 #[derive(Error, Debug)]
 pub enum SpoolsError {
-    #[error("{0} not found")]
-    NotFound(Types),
-    #[error("endpoint returned invalid response")]
-    InvalidResponse,
-    #[error("endpoint returned the following errors: {0}")]
-    ResponseError(String),
-    #[error("unable to fetch request: {0}")]
-    RequestError(reqwest::Error),
+    #[error("{kind} {id} not found")]
+    NotFound { kind: Types, id: String },
+    #[error("{kind} {id} is {reason}")]
+    Unavailable {
+        kind: Types,
+        id: String,
+        reason: Restriction,
+    },
+    #[error("{operation} returned invalid response")]
+    InvalidResponse { operation: String },
+    #[error("{0} is not a valid post identifier")]
+    InvalidId(String),
+    #[error("{0} is not a Threads URL or handle")]
+    InvalidReference(String),
+    #[error("{operation} returned a response missing expected field {pointer}")]
+    MissingField { operation: String, pointer: String },
+    #[error("{operation} returned field {pointer} with an unexpected type: {message}")]
+    InvalidField {
+        operation: String,
+        pointer: String,
+        message: String,
+    },
+    #[error("{operation} returned the following errors: {}", format_errors(.errors))]
+    ResponseError {
+        operation: String,
+        errors: Vec<MetaError>,
+    },
+    #[error("{operation} returned HTTP status {status}")]
+    StatusError { operation: String, status: u16 },
+    #[error("unable to fetch {operation}: {source}")]
+    RequestError {
+        operation: String,
+        source: reqwest::Error,
+    },
+    #[error("{operation} was rate limited")]
+    RateLimited {
+        operation: String,
+        /// Seconds to wait before retrying, if Meta told us
+        retry_after: Option<u64>,
+    },
+    #[error("{operation} was blocked by a {wall} page")]
+    Blocked {
+        operation: String,
+        wall: Interstitial,
+    },
+    #[error("{operation} uses a document ID Threads no longer recognizes")]
+    StaleDocId { operation: String },
     #[error("couldn't build client")]
     ClientError,
-    #[error("couldn't build subpost")]
-    SubpostError,
+    #[error("media has unexpected content type: {0}")]
+    ContentTypeError(String),
+    #[error("download ended early: expected {expected} bytes, received {received}")]
+    IncompleteDownload { expected: u64, received: u64 },
+    #[error("unable to write download: {0}")]
+    WriteError(std::io::Error),
 }
 
 /// Possible objects to be fetched on a request. Used for errors.
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Types {
     Post,
     User,
@@ -38,26 +85,406 @@ impl fmt::Display for Types {
     }
 }
 
+/// Reason why an existing object can't be viewed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Restriction {
+    Private,
+    Suspended,
+    Deleted,
+    /// Blocked in the country the request comes from
+    RegionRestricted,
+    /// Only viewable by logged in adults
+    AgeRestricted,
+}
+
+impl fmt::Display for Restriction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> std::fmt::Result {
+        let out = match self {
+            Restriction::Private => "private",
+            Restriction::Suspended => "suspended",
+            Restriction::Deleted => "deleted",
+            Restriction::RegionRestricted => "restricted in this region",
+            Restriction::AgeRestricted => "age restricted",
+        };
+
+        write!(f, "{}", out)
+    }
+}
+
+impl Restriction {
+    /// Look for the wording Threads uses for restricted content within an error message or title
+    ///
+    /// Only short, specific text should be checked, since pages are full of unrelated strings.
+    pub(crate) fn detect(text: &str) -> Option<Self> {
+        let text = text.to_lowercase();
+        let markers = [
+            (Restriction::Private, "this account is private"),
+            (Restriction::Private, "this profile is private"),
+            (Restriction::Suspended, "account has been suspended"),
+            (Restriction::Suspended, "account was suspended"),
+            (Restriction::Suspended, "account has been disabled"),
+            (Restriction::Deleted, "has been deleted"),
+            (Restriction::Deleted, "was deleted"),
+            (
+                Restriction::RegionRestricted,
+                "not available in your country",
+            ),
+            (Restriction::RegionRestricted, "restricted in your country"),
+            (
+                Restriction::RegionRestricted,
+                "not available in your region",
+            ),
+            (Restriction::AgeRestricted, "age-restricted"),
+            (Restriction::AgeRestricted, "age restricted"),
+            (Restriction::AgeRestricted, "must be 18 or older"),
+        ];
+
+        markers
+            .iter()
+            .find(|(_, marker)| text.contains(marker))
+            .map(|(restriction, _)| *restriction)
+    }
+}
+
+/// Get the text within an HTML page's title element
+fn page_title(page: &str) -> Option<&str> {
+    let (_, rest) = page.split_once("<title")?;
+    let (_, rest) = rest.split_once('>')?;
+    let (title, _) = rest.split_once("</title>")?;
+
+    Some(title)
+}
+
+/// Page served by Meta in place of the requested data
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Interstitial {
+    /// Login form or redirect to one, or a response asking to log in again
+    Login,
+    /// Challenge asking to verify the account or browser
+    Checkpoint,
+    /// Anything else which isn't the expected response, such as an HTML page from the API
+    Other,
+}
+
+impl fmt::Display for Interstitial {
+    fn fmt(&self, f: &mut fmt::Formatter) -> std::fmt::Result {
+        let out = match self {
+            Interstitial::Login => "login",
+            Interstitial::Checkpoint => "checkpoint",
+            Interstitial::Other => "interstitial",
+        };
+
+        write!(f, "{}", out)
+    }
+}
+
+impl Interstitial {
+    /// Look for a login wall or checkpoint, given the final URL of a request and its body
+    pub(crate) fn detect(url: &str, body: &str) -> Option<Self> {
+        if ["/challenge", "/checkpoint"]
+            .iter()
+            .any(|path| url.contains(path))
+            || ["checkpoint_required", "challenge_required"]
+                .iter()
+                .any(|marker| body.contains(marker))
+        {
+            Some(Interstitial::Checkpoint)
+        } else if url.contains("/login") || body.contains("login_required") {
+            Some(Interstitial::Login)
+        } else {
+            None
+        }
+    }
+}
+
+/// Error returned by Meta within a GraphQL response
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct MetaError {
+    pub code: Option<u64>,
+    pub summary: Option<String>,
+    pub description: Option<String>,
+    pub message: Option<String>,
+    pub is_transient: Option<bool>,
+    pub requires_reauth: Option<bool>,
+}
+
+/// Meta error code for exceeding a rate limit
+const RATE_LIMIT_CODE: u64 = 1675004;
+
+/// Meta error code for a document ID which doesn't belong to any query
+const UNKNOWN_DOC_ID_CODE: u64 = 1675002;
+
+impl fmt::Display for MetaError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> std::fmt::Result {
+        let text = self
+            .summary
+            .as_deref()
+            .or(self.message.as_deref())
+            .unwrap_or_default();
+
+        match self.code {
+            Some(code) => write!(f, "{} ({})", text, code),
+            None => write!(f, "{}", text),
+        }
+    }
+}
+
+/// Join Meta errors in a single line
+fn format_errors(errors: &[MetaError]) -> String {
+    errors
+        .iter()
+        .map(|err| format!("{};", err))
+        .collect::<String>()
+}
+
+/// Location of a node within a response, used to report fields missing from it
+#[derive(Debug, Clone)]
+pub(crate) struct Location<'a> {
+    operation: &'a str,
+    pointer: String,
+}
+
+impl<'a> Location<'a> {
+    /// Location of a node, given the operation it was returned by and its JSON pointer
+    pub(crate) fn new(operation: &'a str, pointer: impl fmt::Display) -> Self {
+        Location {
+            operation,
+            pointer: pointer.to_string(),
+        }
+    }
+
+    /// Location of a node within this one, given its path relative to it
+    pub(crate) fn join(&self, path: impl fmt::Display) -> Self {
+        Location::new(self.operation, format!("{}/{}", self.pointer, path))
+    }
+
+    /// Build an error for a field missing from the node, given its path relative to it
+    pub(crate) fn missing(&self, path: &str) -> SpoolsError {
+        SpoolsError::missing(self.operation, &format!("{}{}", self.pointer, path))
+    }
+}
+
 impl SpoolsError {
-    pub(crate) fn deserialize_error(response: Value) -> Self {
+    /// Build an error for a JSON pointer which couldn't be found in a response
+    pub(crate) fn missing(operation: &str, pointer: &str) -> Self {
+        SpoolsError::MissingField {
+            operation: operation.to_string(),
+            pointer: pointer.to_string(),
+        }
+    }
+
+    /// Build an error for a response which couldn't be understood
+    pub(crate) fn invalid(operation: &str) -> Self {
+        SpoolsError::InvalidResponse {
+            operation: operation.to_string(),
+        }
+    }
+
+    /// Build an error for a request which failed before getting a response
+    pub(crate) fn request(operation: &str) -> impl FnOnce(reqwest::Error) -> Self + '_ {
+        move |source| SpoolsError::RequestError {
+            operation: operation.to_string(),
+            source,
+        }
+    }
+
+    /// Build an error for an unsuccessful HTTP status
+    pub(crate) fn status(operation: &str, status: StatusCode, retry_after: Option<u64>) -> Self {
+        let operation = operation.to_string();
+
+        match status {
+            StatusCode::TOO_MANY_REQUESTS => SpoolsError::RateLimited {
+                operation,
+                retry_after,
+            },
+            StatusCode::UNAUTHORIZED => SpoolsError::Blocked {
+                operation,
+                wall: Interstitial::Login,
+            },
+            _ => SpoolsError::StatusError {
+                operation,
+                status: status.as_u16(),
+            },
+        }
+    }
+
+    /// Build an error for an unsuccessful HTTP status, keeping any Meta errors sent in its body
+    pub(crate) fn status_with_body(
+        operation: &str,
+        status: StatusCode,
+        retry_after: Option<u64>,
+        body: &str,
+    ) -> Self {
+        let errors = serde_json::from_str::<Value>(body)
+            .ok()
+            .filter(|json| json.get("errors").is_some());
+
+        match errors.map(|json| SpoolsError::deserialize_error(operation, json)) {
+            Some(SpoolsError::RateLimited { operation, .. }) => SpoolsError::RateLimited {
+                operation,
+                retry_after,
+            },
+            Some(SpoolsError::InvalidResponse { .. }) | None => {
+                SpoolsError::status(operation, status, retry_after)
+            }
+            Some(error) => error,
+        }
+    }
+
+    /// Build an error out of the errors Meta returned within a GraphQL response
+    pub(crate) fn deserialize_error(operation: &str, response: Value) -> Self {
         let maybe_error = response.pointer("/errors");
 
         if let Some(Value::Array(error_array)) = maybe_error {
-            SpoolsError::ResponseError(error_array.iter().fold(String::new(), |mut out, err| {
-                let _ = write!(
-                    out,
-                    "{};",
-                    err.pointer("/summary")
-                        .unwrap_or(&Value::Null)
-                        .as_str()
-                        .to_owned()
-                        .unwrap_or_default()
-                );
-
-                out
-            }))
+            let errors: Vec<MetaError> = error_array
+                .iter()
+                .map(|err| MetaError::deserialize(err).unwrap_or_default())
+                .collect();
+            let operation = operation.to_string();
+
+            // Pick out the errors we know how to react to
+            if errors.iter().any(|err| err.code == Some(RATE_LIMIT_CODE)) {
+                SpoolsError::RateLimited {
+                    operation,
+                    retry_after: None,
+                }
+            } else if errors
+                .iter()
+                .any(|err| err.code == Some(UNKNOWN_DOC_ID_CODE))
+            {
+                SpoolsError::StaleDocId { operation }
+            } else if errors.iter().any(|err| err.requires_reauth == Some(true)) {
+                SpoolsError::Blocked {
+                    operation,
+                    wall: Interstitial::Login,
+                }
+            } else {
+                SpoolsError::ResponseError { operation, errors }
+            }
         } else {
-            SpoolsError::InvalidResponse
+            SpoolsError::invalid(operation)
+        }
+    }
+
+    /// Build an error for an object which couldn't be found or viewed, given the page Threads
+    /// returned in its place
+    ///
+    /// Only the page's title is checked for restrictions.
+    pub(crate) fn not_found(kind: Types, id: &str, page: &str) -> Self {
+        let id = id.to_string();
+
+        match page_title(page).and_then(Restriction::detect) {
+            Some(reason) => SpoolsError::Unavailable { kind, id, reason },
+            None => SpoolsError::NotFound { kind, id },
+        }
+    }
+
+    /// Turn an error for a response without data into one about the requested object
+    ///
+    /// Responses which carry no errors at all are treated as the object not existing, while
+    /// errors mentioning a restriction are reported as such. Anything else is kept as-is.
+    pub(crate) fn for_object(self, kind: Types, id: &str) -> Self {
+        match self {
+            SpoolsError::InvalidResponse { .. } => SpoolsError::NotFound {
+                kind,
+                id: id.to_string(),
+            },
+            SpoolsError::ResponseError { ref errors, .. } => {
+                let text = errors
+                    .iter()
+                    .flat_map(|err| [&err.summary, &err.description, &err.message])
+                    .flatten()
+                    .cloned()
+                    .collect::<Vec<String>>()
+                    .join("\n");
+
+                match Restriction::detect(&text) {
+                    Some(reason) => SpoolsError::Unavailable {
+                        kind,
+                        id: id.to_string(),
+                        reason,
+                    },
+                    None => self,
+                }
+            }
+            _ => self,
+        }
+    }
+
+    /// Identifier of the object which couldn't be found or viewed, if any
+    pub fn id(&self) -> Option<&str> {
+        match self {
+            SpoolsError::NotFound { id, .. } | SpoolsError::Unavailable { id, .. } => Some(id),
+            _ => None,
+        }
+    }
+
+    /// Reason why an object can't be viewed, if it exists but is restricted
+    pub fn restriction(&self) -> Option<Restriction> {
+        match self {
+            SpoolsError::Unavailable { reason, .. } => Some(*reason),
+            _ => None,
+        }
+    }
+
+    /// Operation which failed, if the error came from a request
+    pub fn operation(&self) -> Option<&str> {
+        match self {
+            SpoolsError::InvalidResponse { operation }
+            | SpoolsError::MissingField { operation, .. }
+            | SpoolsError::InvalidField { operation, .. }
+            | SpoolsError::ResponseError { operation, .. }
+            | SpoolsError::StatusError { operation, .. }
+            | SpoolsError::RequestError { operation, .. }
+            | SpoolsError::RateLimited { operation, .. }
+            | SpoolsError::Blocked { operation, .. }
+            | SpoolsError::StaleDocId { operation } => Some(operation),
+            _ => None,
+        }
+    }
+
+    /// HTTP status returned by Threads, if any
+    pub fn http_status(&self) -> Option<u16> {
+        match self {
+            SpoolsError::StatusError { status, .. } => Some(*status),
+            SpoolsError::RequestError { source, .. } => source.status().map(|val| val.as_u16()),
+            SpoolsError::RateLimited { .. } => Some(StatusCode::TOO_MANY_REQUESTS.as_u16()),
+            _ => None,
+        }
+    }
+
+    /// JSON pointer which couldn't be found or had an unexpected type, if any
+    pub fn pointer(&self) -> Option<&str> {
+        match self {
+            SpoolsError::MissingField { pointer, .. }
+            | SpoolsError::InvalidField { pointer, .. } => Some(pointer),
+            _ => None,
+        }
+    }
+
+    /// Errors returned by Meta within a GraphQL response, if any
+    pub fn meta_errors(&self) -> &[MetaError] {
+        match self {
+            SpoolsError::ResponseError { errors, .. } => errors,
+            _ => &[],
+        }
+    }
+
+    /// Whether retrying the same request later could succeed
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            SpoolsError::RateLimited { .. } | SpoolsError::IncompleteDownload { .. } => true,
+            SpoolsError::StatusError { status, .. } => *status >= 500,
+            SpoolsError::RequestError { source, .. } => {
+                source.is_timeout()
+                    || source.is_connect()
+                    || source.status().is_some_and(|val| val.is_server_error())
+            }
+            SpoolsError::ResponseError { errors, .. } => {
+                errors.iter().any(|err| err.is_transient == Some(true))
+            }
+            _ => false,
         }
     }
 }
@@ -0,0 +1,135 @@
+use core::fmt;
+use std::str::FromStr;
+
+use crate::error::SpoolsError;
+use serde::{Deserialize, Serialize};
+
+/// Instagram's base64 alphabet, used to encode post IDs into shortcodes
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+/// Short identifier for a post, as found in its URL
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Shortcode(String);
+
+/// Numeric identifier for a post, as used by Threads' API
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct PostId(u64);
+
+impl Shortcode {
+    /// Shortcode as text
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Convert the shortcode into the numeric ID it encodes
+    ///
+    /// Fails for codes which don't map back to themselves, such as the longer codes used for
+    /// private posts, codes with leading zero digits, or codes too large for an ID.
+    pub fn to_id(&self) -> Result<PostId, SpoolsError> {
+        PostId::try_from(self)
+    }
+}
+
+impl PostId {
+    /// Wrap a numeric ID
+    pub fn new(id: u64) -> Self {
+        PostId(id)
+    }
+
+    /// ID as a number
+    pub fn get(&self) -> u64 {
+        self.0
+    }
+
+    /// Convert the ID into its shortcode
+    pub fn to_shortcode(&self) -> Shortcode {
+        Shortcode::from(*self)
+    }
+}
+
+impl From<u64> for PostId {
+    fn from(id: u64) -> Self {
+        PostId(id)
+    }
+}
+
+impl From<PostId> for Shortcode {
+    fn from(id: PostId) -> Self {
+        let mut rest = id.0;
+        let mut code: Vec<u8> = vec![];
+
+        loop {
+            code.push(ALPHABET[(rest % 64) as usize]);
+            rest /= 64;
+
+            if rest == 0 {
+                break;
+            }
+        }
+
+        code.reverse();
+
+        Shortcode(String::from_utf8(code).unwrap_or_default())
+    }
+}
+
+impl TryFrom<&Shortcode> for PostId {
+    type Error = SpoolsError;
+
+    fn try_from(code: &Shortcode) -> Result<Self, Self::Error> {
+        let invalid = || SpoolsError::InvalidId(code.0.to_owned());
+
+        // A leading zero digit would be lost when converting back
+        if code.0.len() > 1 && code.0.starts_with('A') {
+            return Err(invalid());
+        }
+
+        code.0
+            .bytes()
+            .try_fold(0u64, |id, char| {
+                let digit = ALPHABET.iter().position(|val| *val == char)? as u64;
+
+                id.checked_mul(64)?.checked_add(digit)
+            })
+            .map(PostId)
+            .ok_or_else(invalid)
+    }
+}
+
+impl FromStr for Shortcode {
+    type Err = SpoolsError;
+
+    fn from_str(code: &str) -> Result<Self, Self::Err> {
+        if !code.is_empty() && code.bytes().all(|char| ALPHABET.contains(&char)) {
+            Ok(Shortcode(code.to_string()))
+        } else {
+            Err(SpoolsError::InvalidId(code.to_string()))
+        }
+    }
+}
+
+impl FromStr for PostId {
+    type Err = SpoolsError;
+
+    fn from_str(id: &str) -> Result<Self, Self::Err> {
+        // IDs are sometimes suffixed with their author's ID
+        let number = id.split('_').next().unwrap_or_default();
+
+        number
+            .parse::<u64>()
+            .map(PostId)
+            .map_err(|_| SpoolsError::InvalidId(id.to_string()))
+    }
+}
+
+impl fmt::Display for Shortcode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl fmt::Display for PostId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
@@ -16,17 +16,30 @@
 //! let post = client.fetch_post(&user.posts[0].code).await?;
 //! #     Ok(())
 //! # }
+mod attachment;
+mod cdn;
+mod conversation;
+mod entity;
 mod error;
+mod id;
 mod media;
 mod post;
+mod raw;
+mod reference;
 mod threads;
 mod user;
 
-pub use error::SpoolsError;
-pub use media::{Media, MediaKind};
-pub use post::{Post, Subpost};
+pub use attachment::{LinkPreview, Poll, PollOption};
+pub use conversation::{Conversation, Reply};
+pub use entity::{Entity, EntityKind, Segment, Segments};
+pub use error::{Interstitial, MetaError, Restriction, SpoolsError, Types};
+pub use id::{PostId, Shortcode};
+pub use media::{Media, MediaKind, Rendition, Representation, VideoInfo};
+pub use post::{Interactions, Post, Quote, ReplyControl, Subpost};
+pub use reference::{Resolved, ThreadsRef};
 pub use threads::Threads;
 pub use user::{Author, User};
 
 #[cfg(test)]
+#[allow(clippy::bool_assert_comparison, clippy::len_zero)]
 mod test;
@@ -1,12 +1,28 @@
-use crate::SpoolsError;
+use crate::{cdn, error::Location, raw, SpoolsError};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
-/// Whether media is image or video
-#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+/// Kind of media attached to a post
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub enum MediaKind {
     Image,
     Video,
+    /// GIF sticker provided by GIPHY
+    Gif {
+        id: Option<String>,
+        width: Option<u64>,
+        height: Option<u64>,
+    },
+    /// Audio clip or voice note
+    Audio {
+        duration_ms: Option<u64>,
+        waveform: Vec<f64>,
+    },
+    /// Attachment type not yet known to spools, along with its JSON node as returned by Threads
+    ///
+    /// For attachments which aren't part of a carousel, this is the post's node, since Threads
+    /// keeps their fields on the post itself.
+    Unknown(Value),
 }
 
 /// Media location and metadata
@@ -16,82 +32,353 @@ pub struct Media {
     pub alt: Option<String>,
     pub content: String,
     pub thumbnail: String,
+    pub images: Vec<Rendition>,
+    pub videos: Vec<Rendition>,
+    pub video: Option<VideoInfo>,
+}
+
+/// Version of a piece of media at a specific size
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct Rendition {
+    pub url: String,
+    pub width: u64,
+    pub height: u64,
+    /// Instagram's internal identifier for the encoding, only provided for videos
+    pub kind: Option<u64>,
+}
+
+/// Playback metadata for videos
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct VideoInfo {
+    /// Duration in seconds
+    pub duration: Option<f64>,
+    pub has_audio: Option<bool>,
+    pub views: Option<u64>,
+    /// Streams listed in the video's DASH manifest
+    pub representations: Vec<Representation>,
+}
+
+/// Stream within a DASH manifest
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct Representation {
+    pub id: Option<String>,
+    pub mime_type: Option<String>,
+    pub codecs: Option<String>,
+    pub bandwidth: Option<u64>,
+    pub width: Option<u64>,
+    pub height: Option<u64>,
+    pub url: Option<String>,
+}
+
+impl VideoInfo {
+    /// Converts the response model for videos into the VideoInfo data type
+    fn from(object: &raw::Media) -> Self {
+        VideoInfo {
+            duration: object.video_duration,
+            has_audio: object.has_audio,
+            views: object.view_count.or(object.play_count),
+            representations: object
+                .video_dash_manifest
+                .as_deref()
+                .map(Representation::from_manifest)
+                .unwrap_or_default(),
+        }
+    }
+}
+
+impl Representation {
+    /// Parses the representations out of a DASH manifest
+    ///
+    /// Only the bits we need are read, so this doesn't attempt to be a full XML parser. MIME
+    /// types and codecs are inherited from the adaptation set when not set on the stream itself.
+    fn from_manifest(manifest: &str) -> Vec<Self> {
+        let mut representations: Vec<Representation> = vec![];
+        let mut adaptation: Vec<(String, String)> = vec![];
+
+        for chunk in manifest.split('<').skip(1) {
+            let (tag, text) = chunk.split_once('>').unwrap_or((chunk, ""));
+            let name = tag.split_whitespace().next().unwrap_or_default();
+
+            match name {
+                "AdaptationSet" => adaptation = attributes(tag),
+                "/AdaptationSet" => adaptation.clear(),
+                "Representation" | "Representation/" => {
+                    let attrs = attributes(tag);
+                    let get = |key: &str| {
+                        attrs
+                            .iter()
+                            .chain(adaptation.iter())
+                            .find(|(name, _)| name == key)
+                            .map(|(_, value)| value.to_owned())
+                    };
+
+                    representations.push(Representation {
+                        id: get("id"),
+                        mime_type: get("mimeType"),
+                        codecs: get("codecs"),
+                        bandwidth: get("bandwidth").and_then(|val| val.parse().ok()),
+                        width: get("width").and_then(|val| val.parse().ok()),
+                        height: get("height").and_then(|val| val.parse().ok()),
+                        url: None,
+                    })
+                }
+                "BaseURL" => {
+                    if let Some(last) = representations.last_mut() {
+                        last.url = Some(unescape(text.trim()));
+                    }
+                }
+                _ => (),
+            }
+        }
+
+        representations
+    }
+}
+
+/// Collects the attributes within an XML tag
+fn attributes(tag: &str) -> Vec<(String, String)> {
+    let mut out: Vec<(String, String)> = vec![];
+    let mut rest = tag;
+
+    while let Some((key, value)) = rest.split_once("=\"") {
+        let name = key.split_whitespace().last().unwrap_or_default();
+
+        match value.split_once('"') {
+            Some((value, tail)) => {
+                out.push((name.to_string(), unescape(value)));
+                rest = tail;
+            }
+            None => break,
+        }
+    }
+
+    out
+}
+
+/// Replaces the XML entities Meta uses within manifests
+fn unescape(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+impl Rendition {
+    /// Converts the response models for media versions into Rendition data types
+    fn from_versions(versions: &[raw::Version]) -> Vec<Self> {
+        versions
+            .iter()
+            .filter_map(|version| {
+                Some(Rendition {
+                    url: version.url.to_owned()?,
+                    width: version.width.unwrap_or(0),
+                    height: version.height.unwrap_or(0),
+                    kind: version.kind,
+                })
+            })
+            .collect()
+    }
 }
 
 impl Media {
-    /// Serializes JSON structure for attachments into the Media data type
-    pub(crate) fn from(object: Value) -> Result<Self, SpoolsError> {
-        // Set initial values
-        let mut kind = MediaKind::Image;
-        let content: String;
-        let mut alt: Option<String> = None;
-        let thumbnail: String;
+    /// Every URL the media can be loaded from, including renditions and DASH streams
+    fn urls(&self) -> impl Iterator<Item = &str> {
+        let renditions = self.images.iter().chain(&self.videos);
+        let streams = self
+            .video
+            .iter()
+            .flat_map(|video| &video.representations)
+            .filter_map(|stream| stream.url.as_deref());
+
+        [self.content.as_str(), self.thumbnail.as_str()]
+            .into_iter()
+            .chain(renditions.map(|rendition| rendition.url.as_str()))
+            .chain(streams)
+    }
+
+    /// Earliest time at which the media's URLs expire, as a UNIX timestamp
+    pub fn expires_at(&self) -> Option<u64> {
+        self.urls().filter_map(cdn::expiry).min()
+    }
+
+    /// Whether any of the media's URLs have expired
+    pub fn is_expired(&self) -> bool {
+        self.urls().any(cdn::is_expired)
+    }
+
+    /// Pick the rendition closest in size to the given dimensions
+    ///
+    /// Video renditions are used for videos, and image renditions for everything else.
+    pub fn closest(&self, width: u64, height: u64) -> Option<&Rendition> {
+        let renditions = match self.kind {
+            MediaKind::Video => &self.videos,
+            _ => &self.images,
+        };
+
+        renditions.iter().min_by_key(|rendition| {
+            rendition.width.abs_diff(width) + rendition.height.abs_diff(height)
+        })
+    }
+
+    /// Whether a response model contains an attachment which can be parsed as media
+    pub(crate) fn is_attachment(object: &raw::Media) -> bool {
+        let candidates = object
+            .image_versions2
+            .as_ref()
+            .map(|versions| versions.candidates.as_slice())
+            .unwrap_or_default();
+
+        // Text posts are the only ones without an attachment of some sort
+        let typed = !matches!(object.media_type, None | Some(19));
+
+        !candidates.is_empty()
+            || typed
+            || object.giphy_media_info.is_some()
+            || object.audio.is_some()
+    }
+
+    /// Converts the response model for attachments into the Media data type
+    pub(crate) fn from(object: &raw::Media, at: &Location) -> Result<Self, SpoolsError> {
+        // Alt text
+        let alt = object.accessibility_caption.to_owned();
 
         // Locations for media media
-        let video_location = object.pointer("/video_versions").unwrap_or(&Value::Null);
-        let image_location = object
-            .pointer("/image_versions2/candidates")
-            .unwrap_or(&Value::Null);
+        let video_versions = object.video_versions.as_deref().unwrap_or_default();
+        let image_versions = object
+            .image_versions2
+            .as_ref()
+            .map(|versions| versions.candidates.as_slice())
+            .unwrap_or_default();
 
-        // Gets the first image in URL, since it's in the highest quality
-        let image_array = image_location.as_array().unwrap();
+        // GIFs are served as animated images through Instagram's own GIPHY proxy
+        if let Some(giphy) = &object.giphy_media_info {
+            let images = &giphy.first_party_cdn_proxied_images;
+            let animated = images.fixed_height.to_owned().unwrap_or_default();
 
-        let main_image = &image_array[0];
+            return Ok(Media {
+                kind: MediaKind::Gif {
+                    id: giphy.giphy_id.to_owned(),
+                    width: animated.width,
+                    height: animated.height,
+                },
+                alt,
+                content: animated
+                    .webp
+                    .to_owned()
+                    .or(animated.url.to_owned())
+                    .unwrap_or_default(),
+                thumbnail: images
+                    .fixed_height_still
+                    .as_ref()
+                    .and_then(|still| still.url.to_owned())
+                    .or(animated.url)
+                    .unwrap_or_default(),
+                images: vec![],
+                videos: vec![],
+                video: None,
+            });
+        }
+
+        // Audio has no visual counterpart, so the thumbnail is only set if there's a cover image
+        if let Some(audio) = &object.audio {
+            return Ok(Media {
+                kind: MediaKind::Audio {
+                    duration_ms: audio.duration_ms,
+                    waveform: audio.waveform_data.to_owned(),
+                },
+                alt,
+                content: audio.audio_src.to_owned().unwrap_or_default(),
+                thumbnail: image_versions
+                    .first()
+                    .and_then(|image| image.url.to_owned())
+                    .unwrap_or_default(),
+                images: Rendition::from_versions(image_versions),
+                videos: vec![],
+                video: None,
+            });
+        }
 
-        let image = main_image["url"].as_str().to_owned().unwrap().to_string();
+        // Anything else without images isn't something we know how to handle
+        let Some(main_image) = image_versions.first() else {
+            return Ok(Media {
+                kind: MediaKind::Unknown(object.node.to_owned()),
+                alt,
+                content: String::new(),
+                thumbnail: String::new(),
+                images: vec![],
+                videos: vec![],
+                video: None,
+            });
+        };
+
+        // Keep every version around, so consumers can pick their own
+        let images = Rendition::from_versions(image_versions);
+        let videos = Rendition::from_versions(video_versions);
+
+        // Gets the first image in URL, since it's in the highest quality
+        let image = main_image
+            .url
+            .to_owned()
+            .ok_or_else(|| at.missing("/image_versions2/candidates/0/url"))?;
 
         // Gets aspect ratio, to find a thumbnail
-        let image_width = main_image.clone()["width"].as_f64().to_owned().unwrap();
-        let image_height = main_image.clone()["height"].as_f64().to_owned().unwrap();
+        let image_width = main_image
+            .width
+            .ok_or_else(|| at.missing("/image_versions2/candidates/0/width"))?
+            as f64;
+        let image_height = main_image
+            .height
+            .ok_or_else(|| at.missing("/image_versions2/candidates/0/height"))?
+            as f64;
         let aspect_ratio = ((image_width / image_height) * 10.0).round();
 
-        let thumbnail_array: Vec<String> = image_array
+        let thumbnail_array: Vec<&Rendition> = images
             .iter()
             .filter(|val| {
-                aspect_ratio
-                    == ((val["width"].as_f64().to_owned().unwrap()
-                        / val["height"].as_f64().to_owned().unwrap())
-                        * 10.0)
-                        .round()
+                val.height > 0
+                    && aspect_ratio == ((val.width as f64 / val.height as f64) * 10.0).round()
             })
-            .map(|val| val["url"].as_str().unwrap().to_string())
             .collect();
 
-        let thumbnail_url = thumbnail_array[thumbnail_array.len() / 3].to_owned();
-
-        // Alt text
-        if object["accessibility_caption"].is_string() {
-            alt = Some(
-                object["accessibility_caption"]
-                    .as_str()
-                    .to_owned()
-                    .unwrap()
-                    .to_string(),
-            );
-        }
+        // Fall back to the main image if no other version shares its aspect ratio
+        let thumbnail = thumbnail_array
+            .get(thumbnail_array.len() / 3)
+            .map(|val| val.url.to_owned())
+            .unwrap_or_else(|| image.to_owned());
 
         // Video
-        if video_location.is_array() {
-            let video_array = video_location.as_array().unwrap();
-            let video = video_array[0]["url"]
-                .as_str()
+        if let Some(main_video) = video_versions.first() {
+            let video = main_video
+                .url
                 .to_owned()
-                .unwrap()
-                .to_string();
+                .ok_or_else(|| at.missing("/video_versions/0/url"))?;
 
-            kind = MediaKind::Video;
-            content = video;
-            thumbnail = thumbnail_url;
+            Ok(Media {
+                kind: MediaKind::Video,
+                alt,
+                content: video,
+                thumbnail,
+                images,
+                videos,
+                video: Some(VideoInfo::from(object)),
+            })
         } else {
-            content = image;
-            thumbnail = thumbnail_url;
-        }
+            // Images are only labeled as such for photo and text posts, or if there's no type at all
+            let kind = match object.media_type {
+                None | Some(1) | Some(19) => MediaKind::Image,
+                Some(_) => MediaKind::Unknown(object.node.to_owned()),
+            };
 
-        Ok(Media {
-            kind,
-            alt,
-            content,
-            thumbnail,
-        })
+            Ok(Media {
+                kind,
+                alt,
+                content: image,
+                thumbnail,
+                images,
+                videos,
+                video: None,
+            })
+        }
     }
 }
@@ -1,16 +1,39 @@
 use crate::error::SpoolsError;
-use crate::{media::Media, user::Author, Threads};
+use crate::{
+    attachment::{LinkPreview, Poll},
+    cdn,
+    entity::{Entity, Segments},
+    media::Media,
+    reference,
+    user::Author,
+    Threads,
+};
 use serde::{Deserialize, Serialize};
 
 /// Post contents, metadata, media and interactions
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct Post {
     pub id: String,
+    pub code: String,
     pub author: Author,
     pub date: u64,
     pub body: String,
+    pub entities: Vec<Entity>,
     pub media: Vec<Media>,
+    /// Same as `interactions.likes`
     pub likes: u64,
+    pub interactions: Interactions,
+    pub quote: Option<Quote>,
+    pub link_preview: Option<LinkPreview>,
+    pub poll: Option<Poll>,
+    pub federated: bool,
+    pub pinned: bool,
+    pub edited: bool,
+    pub paid_partnership: bool,
+    pub reply_control: ReplyControl,
+    pub is_reply: bool,
+    pub reply_to: Option<Author>,
+    pub root: Option<String>,
     pub parents: Vec<Subpost>,
     pub replies: Vec<Subpost>,
 }
@@ -22,16 +45,131 @@ pub struct Subpost {
     pub author: Author,
     pub date: u64,
     pub body: String,
+    pub entities: Vec<Entity>,
     pub media: Vec<Media>,
+    /// Same as `interactions.likes`
     pub likes: u64,
+    pub interactions: Interactions,
+    pub quote: Option<Quote>,
+    pub link_preview: Option<LinkPreview>,
+    pub poll: Option<Poll>,
+    pub federated: bool,
+    pub pinned: bool,
+    pub edited: bool,
+    pub paid_partnership: bool,
+    pub reply_control: ReplyControl,
+    pub is_reply: bool,
+    pub reply_to: Option<Author>,
+    pub root: Option<String>,
+}
+
+/// Post quoted within another post
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub enum Quote {
+    /// Quoted post contents
+    Available(Box<Subpost>),
+    /// Quoted post was deleted or isn't visible without logging in
+    Unavailable,
+}
+
+/// Who is allowed to reply to a post
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+pub enum ReplyControl {
+    #[default]
+    Everyone,
+    Following,
+    Mentioned,
+    /// Setting not yet known to spools
+    Other(String),
+}
+
+impl ReplyControl {
+    pub(crate) fn from(value: &str) -> Self {
+        match value {
+            "everyone" => ReplyControl::Everyone,
+            "accounts_you_follow" => ReplyControl::Following,
+            "mentioned_only" => ReplyControl::Mentioned,
+            other => ReplyControl::Other(other.to_string()),
+        }
+    }
+}
+
+/// Engagement counters for a post
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct Interactions {
+    pub likes: u64,
+    pub replies: u64,
+    pub reposts: u64,
+    pub quotes: u64,
+    pub reshares: u64,
+}
+
+impl Quote {
+    /// Whether any of the quoted post's CDN URLs have expired
+    pub fn is_expired(&self) -> bool {
+        match self {
+            Quote::Available(post) => post.is_expired(),
+            Quote::Unavailable => false,
+        }
+    }
+}
+
+impl Post {
+    /// Canonical URL for the post
+    pub fn url(&self) -> String {
+        reference::post_url(&self.author.username, &self.code)
+    }
+
+    /// Iterate over the post's body as plain text and entity segments
+    pub fn segments(&self) -> Segments<'_> {
+        Segments::new(&self.body, &self.entities)
+    }
+
+    /// Whether any of the post's CDN URLs have expired, including those in parents and replies
+    pub fn is_expired(&self) -> bool {
+        cdn::is_expired(&self.author.pfp)
+            || self.media.iter().any(Media::is_expired)
+            || self.quote.as_ref().is_some_and(Quote::is_expired)
+            || self
+                .link_preview
+                .as_ref()
+                .is_some_and(LinkPreview::is_expired)
+            || self.parents.iter().any(Subpost::is_expired)
+            || self.replies.iter().any(Subpost::is_expired)
+    }
 }
 
 impl Subpost {
-    /// Convert a subpost into its detailed counterpart
+    /// Canonical URL for the post
+    pub fn url(&self) -> String {
+        reference::post_url(&self.author.username, &self.code)
+    }
+
+    /// Iterate over the post's body as plain text and entity segments
+    pub fn segments(&self) -> Segments<'_> {
+        Segments::new(&self.body, &self.entities)
+    }
+
+    /// Whether any of the post's CDN URLs have expired, including those of the quoted post
+    pub fn is_expired(&self) -> bool {
+        cdn::is_expired(&self.author.pfp)
+            || self.media.iter().any(Media::is_expired)
+            || self.quote.as_ref().is_some_and(Quote::is_expired)
+            || self
+                .link_preview
+                .as_ref()
+                .is_some_and(LinkPreview::is_expired)
+    }
+
+    /// Convert a subpost into its detailed counterpart, through a new client
     pub async fn to_post(&self) -> Result<Post, SpoolsError> {
         let client = Threads::new()?;
-        let post = client.fetch_post(&self.code).await?;
 
-        Ok(post)
+        self.to_post_with(&client).await
+    }
+
+    /// Convert a subpost into its detailed counterpart, through an existing client
+    pub async fn to_post_with(&self, client: &Threads) -> Result<Post, SpoolsError> {
+        client.fetch_post(&self.code).await
     }
 }
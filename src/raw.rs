@@ -0,0 +1,444 @@
+//! Response models for Threads' GraphQL API
+//!
+//! These mirror the JSON documents returned by Threads as closely as possible, only covering the
+//! fields spools reads. Every field is optional or defaulted, since Meta is known to leave out or
+//! null fields depending on the post, so missing data is only treated as an error once the
+//! public models are built.
+
+use serde::{Deserialize, Deserializer};
+use serde_json::Value;
+
+/// Deserialize a field, treating `null` as its default value
+fn nullable<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de> + Default,
+{
+    Option::<T>::deserialize(deserializer).map(Option::unwrap_or_default)
+}
+
+/// Top level of a GraphQL response
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct Response<T> {
+    pub data: Option<T>,
+}
+
+/// Data for a user fetched by username
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct UserData {
+    pub xdt_user_by_username: Option<User>,
+}
+
+/// Data for a user fetched by ID
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct UserByIdData {
+    pub user: Option<User>,
+}
+
+/// Data for a user's threads
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct UserThreadsData {
+    #[serde(rename = "mediaData")]
+    pub media_data: Option<Connection>,
+}
+
+/// Data for a post's thread, or the quotes of a post
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct ThreadData {
+    pub data: Option<Connection>,
+}
+
+/// Data for the users who reposted a post
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct RepostersData {
+    pub reposters: Option<UserList>,
+}
+
+/// List of embedded users
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct UserList {
+    #[serde(deserialize_with = "nullable")]
+    pub users: Vec<Author>,
+}
+
+/// User profile
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct User {
+    pub id: Option<String>,
+    pub username: Option<String>,
+    pub full_name: Option<String>,
+    pub biography: Option<String>,
+    pub is_verified: Option<bool>,
+    pub follower_count: Option<u64>,
+    pub profile_pic_url: Option<String>,
+    #[serde(deserialize_with = "nullable")]
+    pub hd_profile_pic_versions: Vec<Version>,
+    #[serde(deserialize_with = "nullable")]
+    pub bio_links: Vec<BioLink>,
+    pub fediverse_sharing_enabled: Option<bool>,
+    pub text_post_app_is_private: Option<bool>,
+    pub is_private: Option<bool>,
+}
+
+/// Link within a user's bio
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct BioLink {
+    pub url: Option<String>,
+}
+
+/// Paginated list of threads
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct Connection {
+    #[serde(deserialize_with = "nullable")]
+    pub edges: Vec<Edge>,
+}
+
+/// Wrapper around a thread
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct Edge {
+    pub node: Option<Thread>,
+}
+
+/// Thread, consisting of one or more posts in order
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct Thread {
+    pub thread_items: Option<Vec<ThreadItem>>,
+}
+
+/// Wrapper around a post within a thread
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct ThreadItem {
+    pub post: Option<Post>,
+}
+
+/// Post contents and metadata
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct Post {
+    pub code: Option<String>,
+    pub taken_at: Option<u64>,
+    #[serde(deserialize_with = "nullable")]
+    pub user: Author,
+    pub caption: Option<Caption>,
+    pub carousel_media: Option<Vec<Media>>,
+    pub like_count: Option<u64>,
+    pub caption_is_edited: Option<bool>,
+    pub is_paid_partnership: Option<bool>,
+    #[serde(deserialize_with = "nullable")]
+    pub text_post_app_info: TextPostAppInfo,
+    #[serde(flatten)]
+    pub media: Media,
+}
+
+/// User embedded within a post
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct Author {
+    pub username: Option<String>,
+    pub profile_pic_url: Option<String>,
+    pub is_verified: Option<bool>,
+}
+
+/// Post body
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct Caption {
+    pub text: Option<String>,
+}
+
+/// Threads-specific post metadata
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct TextPostAppInfo {
+    pub direct_reply_count: Option<u64>,
+    pub repost_count: Option<u64>,
+    pub quote_count: Option<u64>,
+    pub reshare_count: Option<u64>,
+    pub share_info: Option<ShareInfo>,
+    pub link_preview_attachment: Option<LinkPreview>,
+    pub poll_attachment: Option<Poll>,
+    pub text_fragments: Option<TextFragments>,
+    pub is_federated: Option<bool>,
+    pub pinned_post_info: Option<PinnedPostInfo>,
+    pub reply_control: Option<String>,
+    pub reply_to_author: Option<Author>,
+    pub is_reply: Option<bool>,
+}
+
+/// Posts shared within a post
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct ShareInfo {
+    pub quoted_post: Option<Box<Post>>,
+    pub quoted_attachment_post_unavailable: Option<bool>,
+}
+
+/// Link preview card
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct LinkPreview {
+    pub url: Option<String>,
+    pub display_url: Option<String>,
+    pub title: Option<String>,
+    pub image_url: Option<String>,
+}
+
+/// Poll and its tallies
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct Poll {
+    pub tallies: Option<Vec<Tally>>,
+    pub total_votes: Option<u64>,
+    pub expires_at: Option<u64>,
+    pub finished: Option<bool>,
+}
+
+/// Poll option and its votes
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct Tally {
+    pub text: Option<String>,
+    pub count: Option<u64>,
+    pub vote_percentage: Option<f64>,
+}
+
+/// Rich text fragments making up a post's body
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct TextFragments {
+    #[serde(deserialize_with = "nullable")]
+    pub fragments: Vec<Fragment>,
+}
+
+/// Section of a post's body
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct Fragment {
+    pub fragment_type: Option<String>,
+    pub plaintext: Option<String>,
+    pub mention_fragment: Option<MentionFragment>,
+    pub link_fragment: Option<LinkFragment>,
+    pub tag_fragment: Option<TagFragment>,
+}
+
+/// User mentioned in a fragment
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct MentionFragment {
+    #[serde(deserialize_with = "nullable")]
+    pub mentioned_user: Author,
+}
+
+/// Link within a fragment
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct LinkFragment {
+    pub uri: Option<String>,
+}
+
+/// Tag within a fragment
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct TagFragment {
+    pub tag_name: Option<String>,
+}
+
+/// Whether a post is pinned
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct PinnedPostInfo {
+    pub is_pinned_to_profile: Option<bool>,
+}
+
+/// Attachment for a post or carousel item
+///
+/// The node the attachment was read from is kept, so unknown attachments can be handed out as-is.
+/// For attachments outside of a carousel, this is the post's node, since Threads keeps their
+/// fields on the post itself.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct Media {
+    pub media_type: Option<u64>,
+    pub accessibility_caption: Option<String>,
+    pub image_versions2: Option<ImageVersions>,
+    pub video_versions: Option<Vec<Version>>,
+    pub giphy_media_info: Option<GiphyMediaInfo>,
+    pub audio: Option<Audio>,
+    pub video_duration: Option<f64>,
+    pub has_audio: Option<bool>,
+    pub view_count: Option<u64>,
+    pub play_count: Option<u64>,
+    pub video_dash_manifest: Option<String>,
+    #[serde(skip)]
+    pub node: Value,
+}
+
+/// Image versions for a piece of media
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct ImageVersions {
+    #[serde(deserialize_with = "nullable")]
+    pub candidates: Vec<Version>,
+}
+
+/// Single version of an image or video
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct Version {
+    pub url: Option<String>,
+    pub width: Option<u64>,
+    pub height: Option<u64>,
+    #[serde(rename = "type")]
+    pub kind: Option<u64>,
+}
+
+/// GIF provided through GIPHY
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct GiphyMediaInfo {
+    pub giphy_id: Option<String>,
+    #[serde(deserialize_with = "nullable")]
+    pub first_party_cdn_proxied_images: GiphyImages,
+}
+
+/// Sizes a GIF is provided in
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct GiphyImages {
+    pub fixed_height: Option<GiphyImage>,
+    pub fixed_height_still: Option<GiphyImage>,
+}
+
+/// Single size of a GIF
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct GiphyImage {
+    pub url: Option<String>,
+    pub webp: Option<String>,
+    pub width: Option<u64>,
+    pub height: Option<u64>,
+}
+
+/// Audio clip or voice note
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct Audio {
+    pub audio_src: Option<String>,
+    pub duration_ms: Option<u64>,
+    #[serde(deserialize_with = "nullable")]
+    pub waveform_data: Vec<f64>,
+}
+
+/// Response model holding onto parts of the JSON document it was read from
+///
+/// Nodes are filled in by walking the document a second time once it's been deserialized, which
+/// keeps the locations of invalid fields intact and the JSON exactly as Threads sent it.
+pub trait Nodes {
+    /// Keep the JSON for the nodes within the model, given the document it was read from
+    fn keep_nodes(&mut self, _json: &Value) {}
+}
+
+impl<T: Nodes> Nodes for Option<T> {
+    fn keep_nodes(&mut self, json: &Value) {
+        if let Some(value) = self {
+            value.keep_nodes(json);
+        }
+    }
+}
+
+impl<T: Nodes> Nodes for Box<T> {
+    fn keep_nodes(&mut self, json: &Value) {
+        self.as_mut().keep_nodes(json);
+    }
+}
+
+impl<T: Nodes> Nodes for Vec<T> {
+    fn keep_nodes(&mut self, json: &Value) {
+        let nodes = json.as_array().map(Vec::as_slice).unwrap_or_default();
+
+        for (value, node) in self.iter_mut().zip(nodes) {
+            value.keep_nodes(node);
+        }
+    }
+}
+
+impl<T: Nodes> Nodes for Response<T> {
+    fn keep_nodes(&mut self, json: &Value) {
+        self.data.keep_nodes(&json["data"]);
+    }
+}
+
+impl Nodes for UserData {}
+
+impl Nodes for UserByIdData {}
+
+impl Nodes for RepostersData {}
+
+impl Nodes for UserThreadsData {
+    fn keep_nodes(&mut self, json: &Value) {
+        self.media_data.keep_nodes(&json["mediaData"]);
+    }
+}
+
+impl Nodes for ThreadData {
+    fn keep_nodes(&mut self, json: &Value) {
+        self.data.keep_nodes(&json["data"]);
+    }
+}
+
+impl Nodes for Connection {
+    fn keep_nodes(&mut self, json: &Value) {
+        self.edges.keep_nodes(&json["edges"]);
+    }
+}
+
+impl Nodes for Edge {
+    fn keep_nodes(&mut self, json: &Value) {
+        self.node.keep_nodes(&json["node"]);
+    }
+}
+
+impl Nodes for Thread {
+    fn keep_nodes(&mut self, json: &Value) {
+        self.thread_items.keep_nodes(&json["thread_items"]);
+    }
+}
+
+impl Nodes for ThreadItem {
+    fn keep_nodes(&mut self, json: &Value) {
+        self.post.keep_nodes(&json["post"]);
+    }
+}
+
+impl Nodes for Post {
+    fn keep_nodes(&mut self, json: &Value) {
+        // Attachments outside of a carousel are part of the post's own node
+        self.media.keep_nodes(json);
+        self.carousel_media.keep_nodes(&json["carousel_media"]);
+
+        if let Some(share_info) = &mut self.text_post_app_info.share_info {
+            share_info
+                .quoted_post
+                .keep_nodes(&json["text_post_app_info"]["share_info"]["quoted_post"]);
+        }
+    }
+}
+
+impl Nodes for Media {
+    fn keep_nodes(&mut self, json: &Value) {
+        self.node = json.to_owned();
+    }
+}
@@ -0,0 +1,150 @@
+use core::fmt;
+use std::str::FromStr;
+
+use crate::{error::SpoolsError, id::Shortcode, post::Post, user::User};
+
+/// Base URL for links to Threads
+const BASE_URL: &str = "https://www.threads.net";
+
+/// Hosts Threads content can be linked from
+const HOSTS: [&str; 3] = ["threads.net", "threads.com", "instagram.com"];
+
+/// Instagram paths which look like usernames but aren't profiles
+const RESERVED: [&str; 8] = [
+    "p", "reel", "reels", "tv", "stories", "explore", "accounts", "direct",
+];
+
+/// Reference to a user or post, parsed out of a URL or handle
+///
+/// Accepts Threads and Instagram URLs, with or without their scheme, subdomain, query string or
+/// fragment, as well as `@username`, `username` and `@username@threads.net` handles.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ThreadsRef {
+    User(String),
+    Post {
+        /// Author's username, if it was part of the URL
+        username: Option<String>,
+        code: Shortcode,
+    },
+}
+
+/// User or post fetched from a [`ThreadsRef`]
+#[derive(Debug, Clone)]
+pub enum Resolved {
+    User(Box<User>),
+    Post(Box<Post>),
+}
+
+/// Canonical URL for a user's profile
+pub(crate) fn user_url(username: &str) -> String {
+    format!("{}/@{}", BASE_URL, username)
+}
+
+/// Canonical URL for a post
+pub(crate) fn post_url(username: &str, code: &str) -> String {
+    format!("{}/@{}/post/{}", BASE_URL, username, code)
+}
+
+/// Check a username against the characters Threads allows
+fn username(name: &str) -> Option<String> {
+    let valid = !name.is_empty()
+        && name.len() <= 30
+        && name
+            .chars()
+            .all(|char| char.is_ascii_alphanumeric() || char == '.' || char == '_');
+
+    valid.then(|| name.to_lowercase())
+}
+
+impl ThreadsRef {
+    /// Canonical URL for the referenced user or post
+    ///
+    /// Posts without a known author are linked without one, which Threads redirects.
+    pub fn url(&self) -> String {
+        match self {
+            ThreadsRef::User(name) => user_url(name),
+            ThreadsRef::Post {
+                username: Some(name),
+                code,
+            } => post_url(name, code.as_str()),
+            ThreadsRef::Post {
+                username: None,
+                code,
+            } => format!("{}/post/{}", BASE_URL, code),
+        }
+    }
+
+    /// Parse the path of a Threads or Instagram URL
+    fn from_path(host: &str, path: &str) -> Option<Self> {
+        let segments: Vec<&str> = path.split('/').filter(|val| !val.is_empty()).collect();
+
+        match (host, segments.as_slice()) {
+            ("instagram.com", [name]) if !RESERVED.contains(name) => {
+                Some(ThreadsRef::User(username(name)?))
+            }
+            ("instagram.com", _) => None,
+            (_, [name]) => Some(ThreadsRef::User(username(name.strip_prefix('@')?)?)),
+            (_, [name, "post", code, ..]) => Some(ThreadsRef::Post {
+                username: Some(username(name.strip_prefix('@')?)?),
+                code: code.parse().ok()?,
+            }),
+            (_, ["t" | "post", code, ..]) => Some(ThreadsRef::Post {
+                username: None,
+                code: code.parse().ok()?,
+            }),
+            (_, [name, ..]) => Some(ThreadsRef::User(username(name.strip_prefix('@')?)?)),
+            _ => None,
+        }
+    }
+}
+
+impl FromStr for ThreadsRef {
+    type Err = SpoolsError;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let input = input.trim();
+        let invalid = || SpoolsError::InvalidReference(input.to_string());
+
+        let rest = input
+            .strip_prefix("https://")
+            .or(input.strip_prefix("http://"))
+            .unwrap_or(input);
+        let rest = rest.split(['?', '#']).next().unwrap_or_default();
+
+        // Handles, optionally in their ActivityPub form
+        if let Some(handle) = rest.strip_prefix('@') {
+            let name = match handle.split_once('@') {
+                Some((name, host)) if HOSTS.contains(&host) => name,
+                Some(_) => return Err(invalid()),
+                None => handle,
+            };
+
+            return username(name).map(ThreadsRef::User).ok_or_else(invalid);
+        }
+
+        // URLs, with any subdomain stripped off
+        if let Some((host, path)) = rest.split_once('/') {
+            let host = host.to_lowercase();
+            let host = HOSTS
+                .iter()
+                .find(|val| host == **val || host.ends_with(&format!(".{}", val)));
+
+            if let Some(host) = host {
+                return ThreadsRef::from_path(host, path).ok_or_else(invalid);
+            }
+        }
+
+        // Bare usernames, as long as they aren't just a domain
+        if HOSTS.iter().any(|host| rest.to_lowercase().ends_with(host)) {
+            return Err(invalid());
+        }
+
+        username(rest).map(ThreadsRef::User).ok_or_else(invalid)
+    }
+}
+
+impl fmt::Display for ThreadsRef {
+    fn fmt(&self, f: &mut fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.url())
+    }
+}
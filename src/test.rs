@@ -1,4 +1,45 @@
-use crate::{Author, Threads};
+use crate::{
+    cdn,
+    error::Location,
+    raw::{self, Nodes},
+    threads, Author, Conversation, EntityKind, Interstitial, MediaKind, PostId, Quote,
+    ReplyControl, Restriction, Segment, Shortcode, SpoolsError, Subpost, Threads, ThreadsRef,
+    Types,
+};
+use reqwest::StatusCode;
+use serde_json::{json, Value};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpListener,
+};
+
+/// Deserialize a fixture into the response model for a thread item
+fn thread_item(fixture: Value) -> raw::ThreadItem {
+    let mut item: raw::ThreadItem = serde_json::from_value(fixture.to_owned()).unwrap();
+    item.keep_nodes(&fixture);
+
+    item
+}
+
+/// Build a post out of a fixture, filling in the fields every post has unless overridden
+fn subpost(fields: Value) -> Result<Subpost, SpoolsError> {
+    let mut post = json!({
+        "code": "C8kPz1aFiXt",
+        "taken_at": 1718000000,
+        "user": { "username": "zuck", "profile_pic_url": "", "is_verified": true }
+    });
+
+    if let (Some(post), Value::Object(fields)) = (post.as_object_mut(), fields) {
+        post.extend(fields);
+    }
+
+    Threads::build_subpost(&thread_item(json!({ "post": post })), &item_location())
+}
+
+/// Location of the first item of the first thread within a post's thread response
+fn item_location() -> Location<'static> {
+    Location::new("post_thread", "/data/data/edges/0/node/thread_items/0")
+}
 
 #[tokio::test]
 async fn new_client() {
@@ -91,3 +132,1113 @@ async fn convert_to_user() {
     assert_eq!(user.id, 314216);
     assert_eq!(user.verified, true);
 }
+
+#[tokio::test]
+async fn convert_with_client() {
+    let client = Threads::new().unwrap();
+    let post = client.fetch_post("C6brVPxR1fZ").await.unwrap();
+
+    let parent = post.parents[0].to_post_with(&client).await;
+    assert!(parent.is_ok());
+    assert_eq!(parent.unwrap().author.username, "metaquest");
+
+    let author = post.author.to_user_with(&client).await;
+    assert!(author.is_ok());
+    assert_eq!(author.unwrap().id, 314216);
+}
+
+#[tokio::test]
+async fn fetch_quotes_and_reposters() {
+    let client = Threads::new().unwrap();
+    let quotes = client.fetch_quotes("C2QBoRaRmR1").await;
+    println!("{:#?}", quotes);
+    assert!(quotes.is_ok());
+
+    let reposters = client.fetch_reposters("C2QBoRaRmR1").await;
+    println!("{:#?}", reposters);
+    assert!(reposters.is_ok());
+}
+
+#[test]
+fn parse_quotes_and_reposters() {
+    let quotes = json!({
+        "data": {
+            "data": {
+                "edges": [
+                    {
+                        "node": {
+                            "thread_items": [{
+                                "post": {
+                                    "code": "C2QcQuOtE01",
+                                    "taken_at": 1705100000,
+                                    "user": { "username": "mosseri", "is_verified": true },
+                                    "caption": { "text": "Quoting this" }
+                                }
+                            }]
+                        }
+                    },
+                    { "node": { "thread_items": [] } }
+                ]
+            }
+        },
+        "extensions": { "is_final": true }
+    });
+
+    let resp = Threads::build_quotes("C2QBoRaRmR1", quotes).unwrap();
+    assert_eq!(resp.len(), 1);
+    assert_eq!(resp[0].code, "C2QcQuOtE01");
+    assert_eq!(resp[0].author.username, "mosseri");
+
+    let reposters = json!({
+        "data": {
+            "reposters": {
+                "users": [
+                    { "username": "mosseri", "profile_pic_url": "https://example.com/m.jpg", "is_verified": true },
+                    { "username": "someone", "profile_pic_url": null, "is_verified": false }
+                ]
+            }
+        }
+    });
+
+    let resp = Threads::build_reposters("C2QBoRaRmR1", reposters).unwrap();
+    assert_eq!(resp.len(), 2);
+    assert!(resp[0].verified);
+    assert_eq!(resp[1].username, "someone");
+    assert_eq!(resp[1].pfp, "");
+
+    let missing = json!({ "data": { "reposters": null } });
+    let resp = Threads::build_reposters("C2QBoRaRmR1", missing);
+    assert!(matches!(resp, Err(SpoolsError::NotFound { .. })));
+}
+
+#[test]
+fn parse_interactions() {
+    let fixture = json!({
+        "like_count": 120,
+        "text_post_app_info": {
+            "direct_reply_count": 14,
+            "repost_count": 6,
+            "quote_count": 3,
+            "reshare_count": null
+        }
+    });
+
+    let post = subpost(fixture).unwrap();
+    assert_eq!(post.likes, 120);
+    assert_eq!(post.interactions.likes, 120);
+    assert_eq!(post.interactions.replies, 14);
+    assert_eq!(post.interactions.reposts, 6);
+    assert_eq!(post.interactions.quotes, 3);
+    assert_eq!(post.interactions.reshares, 0);
+
+    let empty = subpost(json!({})).unwrap();
+    assert_eq!(empty.likes, 0);
+    assert_eq!(empty.interactions.replies, 0);
+}
+
+#[test]
+fn parse_link_preview() {
+    let fixture = json!({
+        "text_post_app_info": {
+            "link_preview_attachment": {
+                "url": "https://l.threads.net/?u=https%3A%2F%2Fexample.com%2Farticle",
+                "display_url": "example.com",
+                "title": "An article",
+                "image_url": "https://example.com/card.jpg"
+            }
+        }
+    });
+
+    let preview = subpost(fixture).unwrap().link_preview.unwrap();
+    assert_eq!(preview.display_url, "example.com");
+    assert_eq!(preview.title.as_deref(), Some("An article"));
+    assert_eq!(
+        preview.image.as_deref(),
+        Some("https://example.com/card.jpg")
+    );
+
+    // Empty titles and images are dropped, and the full URL is shown if there's no short one
+    let bare = json!({
+        "text_post_app_info": {
+            "link_preview_attachment": {
+                "url": "https://example.com/article",
+                "title": "",
+                "image_url": ""
+            }
+        }
+    });
+
+    let preview = subpost(bare).unwrap().link_preview.unwrap();
+    assert_eq!(preview.display_url, "https://example.com/article");
+    assert_eq!(preview.title, None);
+    assert_eq!(preview.image, None);
+
+    let broken =
+        json!({ "text_post_app_info": { "link_preview_attachment": { "title": "No URL" } } });
+    assert_eq!(
+        subpost(broken).unwrap_err().pointer(),
+        Some("/data/data/edges/0/node/thread_items/0/post/text_post_app_info/link_preview_attachment/url")
+    );
+}
+
+#[test]
+fn parse_fediverse_status() {
+    let profile = |fields: Value| -> raw::User { serde_json::from_value(fields).unwrap() };
+
+    let federated = profile(json!({ "username": "zuck", "fediverse_sharing_enabled": true }));
+    let user = Threads::build_profile("zuck", 314216, &federated, &raw::User::default(), vec![]);
+    assert!(user.fediverse);
+    assert_eq!(
+        user.activitypub_handle.as_deref(),
+        Some("@zuck@threads.net")
+    );
+
+    let local = profile(json!({ "username": "zuck", "fediverse_sharing_enabled": null }));
+    let user = Threads::build_profile("zuck", 314216, &local, &raw::User::default(), vec![]);
+    assert!(!user.fediverse);
+    assert_eq!(user.activitypub_handle, None);
+
+    let post = subpost(json!({ "text_post_app_info": { "is_federated": true } })).unwrap();
+    assert!(post.federated);
+    assert!(!subpost(json!({})).unwrap().federated);
+}
+
+#[test]
+fn parse_post_flags() {
+    let fixture = json!({
+        "caption_is_edited": true,
+        "is_paid_partnership": true,
+        "text_post_app_info": {
+            "pinned_post_info": { "is_pinned_to_profile": true },
+            "reply_control": "accounts_you_follow"
+        }
+    });
+
+    let post = subpost(fixture).unwrap();
+    assert!(post.pinned);
+    assert!(post.edited);
+    assert!(post.paid_partnership);
+    assert_eq!(post.reply_control, ReplyControl::Following);
+
+    let plain = subpost(json!({})).unwrap();
+    assert!(!plain.pinned && !plain.edited && !plain.paid_partnership);
+    assert_eq!(plain.reply_control, ReplyControl::Everyone);
+
+    assert_eq!(ReplyControl::from("everyone"), ReplyControl::Everyone);
+    assert_eq!(
+        ReplyControl::from("mentioned_only"),
+        ReplyControl::Mentioned
+    );
+    assert_eq!(
+        ReplyControl::from("followers_only"),
+        ReplyControl::Other("followers_only".to_string())
+    );
+}
+
+#[test]
+fn parse_reply_metadata() {
+    let post = |code: &str, reply_to: Option<&str>| {
+        subpost(json!({
+            "code": code,
+            "text_post_app_info": {
+                "reply_to_author": reply_to.map(|name| json!({ "username": name }))
+            }
+        }))
+        .unwrap()
+    };
+
+    let reply = post("C8kPz1aRePl", Some("mosseri"));
+    assert!(reply.is_reply);
+    assert_eq!(reply.reply_to.unwrap().username, "mosseri");
+
+    let flagged = subpost(json!({ "text_post_app_info": { "is_reply": true } })).unwrap();
+    assert!(flagged.is_reply);
+    assert!(flagged.reply_to.is_none());
+
+    let mut thread = vec![
+        post("ROOT", None),
+        post("FIRST", Some("zuck")),
+        post("SECOND", Some("mosseri")),
+    ];
+    threads::link_root(&mut thread);
+    assert_eq!(thread[0].root, None);
+    assert_eq!(thread[1].root.as_deref(), Some("ROOT"));
+    assert_eq!(thread[2].root.as_deref(), Some("ROOT"));
+
+    // The root isn't part of threads which start with a reply
+    let mut orphan = vec![post("FIRST", Some("zuck")), post("SECOND", Some("mosseri"))];
+    threads::link_root(&mut orphan);
+    assert!(orphan.iter().all(|post| post.root.is_none()));
+}
+
+#[test]
+fn parse_open_poll() {
+    let fixture = json!({
+        "caption": { "text": "Which one?" },
+        "text_post_app_info": {
+            "poll_attachment": {
+                "tallies": [
+                    { "text": "Threads", "vote_percentage": 0.75 },
+                    { "text": "Instagram", "vote_percentage": 0.25 }
+                ],
+                "expires_at": 1718086400,
+                "finished": false
+            }
+        }
+    });
+
+    let post = subpost(fixture).unwrap();
+    println!("{:#?}", post);
+
+    let poll = post.poll.unwrap();
+    assert!(!poll.closed);
+    assert_eq!(poll.options.len(), 2);
+    assert_eq!(poll.options[0].text, "Threads");
+    assert_eq!(poll.options[0].percentage, Some(0.75));
+    assert_eq!(poll.total_votes, None);
+    assert_eq!(poll.expires_at, Some(1718086400));
+}
+
+#[test]
+fn parse_closed_poll() {
+    let fixture = json!({
+        "caption": { "text": "Which one?" },
+        "text_post_app_info": {
+            "poll_attachment": {
+                "tallies": [
+                    { "text": "Threads", "count": 30 },
+                    { "text": "Instagram", "count": 12 }
+                ],
+                "expires_at": 1718086400,
+                "finished": true
+            }
+        }
+    });
+
+    let post = subpost(fixture).unwrap();
+    println!("{:#?}", post);
+
+    let poll = post.poll.unwrap();
+    assert!(poll.closed);
+    assert_eq!(poll.options[1].votes, Some(12));
+    assert_eq!(poll.total_votes, Some(42));
+}
+
+#[test]
+fn parse_text_entities() {
+    let fixture = json!({
+        "caption": { "text": "héllo @mosseri, see threads.net 🧵 #tag" },
+        "text_post_app_info": {
+            "text_fragments": {
+                "fragments": [
+                    { "fragment_type": "plaintext", "plaintext": "héllo " },
+                    {
+                        "fragment_type": "mention",
+                        "plaintext": "@mosseri",
+                        "mention_fragment": {
+                            "mentioned_user": { "username": "mosseri", "is_verified": true }
+                        }
+                    },
+                    { "fragment_type": "plaintext", "plaintext": ", see " },
+                    {
+                        "fragment_type": "link",
+                        "plaintext": "threads.net",
+                        "link_fragment": { "uri": "https://threads.net/", "display_text": "threads.net" }
+                    },
+                    { "fragment_type": "plaintext", "plaintext": " 🧵 " },
+                    {
+                        "fragment_type": "tag",
+                        "plaintext": "#tag",
+                        "tag_fragment": { "tag_name": "tag" }
+                    }
+                ]
+            }
+        }
+    });
+
+    let post = subpost(fixture).unwrap();
+    println!("{:#?}", post);
+    assert_eq!(post.entities.len(), 3);
+    assert_eq!(&post.body[post.entities[0].range.clone()], "@mosseri");
+    assert!(
+        matches!(&post.entities[0].kind, EntityKind::Mention(user) if user.username == "mosseri")
+    );
+    assert!(
+        matches!(&post.entities[1].kind, EntityKind::Link(url) if url == "https://threads.net/")
+    );
+
+    let segments: Vec<Segment> = post.segments().collect();
+    assert_eq!(segments.len(), 6);
+    assert!(matches!(segments[0], Segment::Text("héllo ")));
+    assert!(matches!(segments[5], Segment::Entity(_, "#tag")));
+    assert_eq!(
+        segments
+            .iter()
+            .map(|segment| match segment {
+                Segment::Text(text) => *text,
+                Segment::Entity(_, text) => *text,
+            })
+            .collect::<String>(),
+        post.body
+    );
+}
+
+#[test]
+fn parse_gif_and_audio_media() {
+    let fixture = json!({
+        "carousel_media": [
+            {
+                "giphy_media_info": {
+                    "giphy_id": "3o7aD2saalBwwftBIY",
+                    "first_party_cdn_proxied_images": {
+                        "fixed_height": {
+                            "url": "https://example.com/giphy.gif",
+                            "webp": "https://example.com/giphy.webp",
+                            "width": 356,
+                            "height": 200
+                        },
+                        "fixed_height_still": { "url": "https://example.com/giphy_s.gif" }
+                    }
+                }
+            },
+            {
+                "audio": {
+                    "audio_src": "https://example.com/voice.m4a",
+                    "duration_ms": 4200,
+                    "waveform_data": [0.1, 0.5, 0.25]
+                }
+            },
+            { "media_type": 42, "sticker": {}, "accessibility_caption": null }
+        ]
+    });
+
+    let post = subpost(fixture).unwrap();
+    println!("{:#?}", post);
+    assert_eq!(post.media.len(), 3);
+
+    assert!(matches!(
+        &post.media[0].kind,
+        MediaKind::Gif { id: Some(id), width: Some(356), .. } if id == "3o7aD2saalBwwftBIY"
+    ));
+    assert_eq!(post.media[0].content, "https://example.com/giphy.webp");
+    assert_eq!(post.media[0].thumbnail, "https://example.com/giphy_s.gif");
+
+    assert!(matches!(
+        &post.media[1].kind,
+        MediaKind::Audio { duration_ms: Some(4200), waveform } if waveform.len() == 3
+    ));
+    assert_eq!(post.media[1].content, "https://example.com/voice.m4a");
+
+    // Unknown attachments are handed out exactly as Threads sent them, nulls included
+    assert!(matches!(
+        &post.media[2].kind,
+        MediaKind::Unknown(raw)
+            if *raw == json!({ "media_type": 42, "sticker": {}, "accessibility_caption": null })
+    ));
+}
+
+#[test]
+fn keep_unknown_single_attachment() {
+    let fixture = json!({ "media_type": 42, "sticker": { "id": "abc" }, "video_versions": null });
+    let post = subpost(fixture).unwrap();
+    assert_eq!(post.media.len(), 1);
+    assert!(matches!(
+        &post.media[0].kind,
+        MediaKind::Unknown(raw) if raw["media_type"] == 42 && raw["sticker"]["id"] == "abc"
+    ));
+
+    // Outside of a carousel, the attachment's node is the post itself
+    assert!(matches!(
+        &post.media[0].kind,
+        MediaKind::Unknown(raw)
+            if raw["code"] == "C8kPz1aFiXt" && raw.get("video_versions") == Some(&Value::Null)
+    ));
+
+    // Text posts don't carry any media
+    assert!(subpost(json!({ "media_type": 19 }))
+        .unwrap()
+        .media
+        .is_empty());
+    assert!(subpost(json!({})).unwrap().media.is_empty());
+}
+
+#[test]
+fn pick_closest_rendition() {
+    let fixture = json!({
+        "media_type": 1,
+        "image_versions2": {
+            "candidates": [
+                { "url": "https://example.com/1440.jpg", "width": 1440, "height": 1800 },
+                { "url": "https://example.com/1080.jpg", "width": 1080, "height": 1350 },
+                { "url": "https://example.com/640.jpg", "width": 640, "height": 800 },
+                { "url": "https://example.com/320.jpg", "width": 320, "height": 400 }
+            ]
+        }
+    });
+
+    let post = subpost(fixture).unwrap();
+    println!("{:#?}", post);
+
+    let media = &post.media[0];
+    assert_eq!(media.kind, MediaKind::Image);
+    assert_eq!(media.images.len(), 4);
+    assert!(media.videos.is_empty());
+    assert_eq!(
+        media.closest(600, 750).unwrap().url,
+        "https://example.com/640.jpg"
+    );
+    assert_eq!(media.closest(4000, 5000).unwrap().width, 1440);
+}
+
+#[test]
+fn parse_video_manifest() {
+    let manifest = r#"<?xml version="1.0"?><MPD xmlns="urn:mpeg:dash:schema:mpd:2011" mediaPresentationDuration="PT12.5S"><Period><AdaptationSet segmentAlignment="true" mimeType="video/mp4"><Representation id="101v" bandwidth="832000" codecs="avc1.4D401F" width="720" height="1280"><BaseURL>https://example.com/video.mp4?a=1&amp;b=2</BaseURL></Representation></AdaptationSet><AdaptationSet mimeType="audio/mp4"><Representation id="101a" bandwidth="64000" codecs="mp4a.40.5"><BaseURL>https://example.com/audio.mp4</BaseURL></Representation></AdaptationSet></Period></MPD>"#;
+    let fixture = json!({
+        "media_type": 2,
+        "image_versions2": {
+            "candidates": [{ "url": "https://example.com/cover.jpg", "width": 720, "height": 1280 }]
+        },
+        "video_versions": [
+            { "type": 101, "url": "https://example.com/video.mp4", "width": 720, "height": 1280 }
+        ],
+        "video_duration": 12.5,
+        "has_audio": true,
+        "play_count": 1200,
+        "video_dash_manifest": manifest
+    });
+
+    let post = subpost(fixture).unwrap();
+    println!("{:#?}", post);
+
+    let media = &post.media[0];
+    assert_eq!(media.kind, MediaKind::Video);
+    assert_eq!(media.videos[0].kind, Some(101));
+
+    let video = media.video.as_ref().unwrap();
+    assert_eq!(video.duration, Some(12.5));
+    assert_eq!(video.has_audio, Some(true));
+    assert_eq!(video.views, Some(1200));
+    assert_eq!(video.representations.len(), 2);
+    assert_eq!(
+        video.representations[0].mime_type.as_deref(),
+        Some("video/mp4")
+    );
+    assert_eq!(video.representations[0].bandwidth, Some(832000));
+    assert_eq!(video.representations[0].height, Some(1280));
+    assert_eq!(
+        video.representations[0].url.as_deref(),
+        Some("https://example.com/video.mp4?a=1&b=2")
+    );
+    assert_eq!(
+        video.representations[1].codecs.as_deref(),
+        Some("mp4a.40.5")
+    );
+}
+
+/// Serve a file over HTTP once per connection, honoring range requests
+async fn serve_media(content_type: &'static str, body: &'static [u8]) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    tokio::spawn(async move {
+        while let Ok((mut stream, _)) = listener.accept().await {
+            let mut buf = vec![0; 4096];
+            let read = stream.read(&mut buf).await.unwrap();
+            let request = String::from_utf8_lossy(&buf[..read]).to_lowercase();
+
+            let offset: usize = request
+                .lines()
+                .find_map(|line| line.strip_prefix("range: bytes="))
+                .and_then(|range| range.trim_end_matches('-').parse().ok())
+                .unwrap_or(0);
+
+            let status = if offset > 0 {
+                "206 Partial Content"
+            } else {
+                "200 OK"
+            };
+            let head = format!(
+                "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                status,
+                content_type,
+                body.len() - offset
+            );
+
+            stream.write_all(head.as_bytes()).await.unwrap();
+            stream.write_all(&body[offset..]).await.unwrap();
+        }
+    });
+
+    format!("http://{}/media.jpg", addr)
+}
+
+#[tokio::test]
+async fn download_media() {
+    let client = Threads::new().unwrap();
+    let url = serve_media("image/jpeg", b"not quite a jpeg").await;
+
+    let mut out: Vec<u8> = vec![];
+    let written = client.download_url(&url, &mut out, 0).await.unwrap();
+    assert_eq!(written, 16);
+    assert_eq!(out, b"not quite a jpeg");
+}
+
+#[tokio::test]
+async fn resume_media_download() {
+    let client = Threads::new().unwrap();
+    let url = serve_media("video/mp4", b"0123456789").await;
+
+    let path = std::env::temp_dir().join(format!("spools-resume-{}", std::process::id()));
+    tokio::fs::write(&path, b"0123").await.unwrap();
+
+    let written = client.download_url_to_file(&url, &path).await.unwrap();
+    let content = tokio::fs::read(&path).await.unwrap();
+    let _ = tokio::fs::remove_file(&path).await;
+
+    assert_eq!(written, 6);
+    assert_eq!(content, b"0123456789");
+}
+
+#[tokio::test]
+async fn reject_non_media_download() {
+    let client = Threads::new().unwrap();
+    let url = serve_media("text/html", b"<html>login</html>").await;
+
+    let mut out: Vec<u8> = vec![];
+    let resp = client.download_url(&url, &mut out, 0).await;
+    assert!(matches!(resp, Err(SpoolsError::ContentTypeError(_))));
+    assert!(out.is_empty());
+
+    // Files created for the download don't stick around after it fails
+    let path = std::env::temp_dir().join(format!("spools-reject-{}", std::process::id()));
+    let resp = client.download_url_to_file(&url, &path).await;
+    assert!(matches!(resp, Err(SpoolsError::ContentTypeError(_))));
+    assert!(!path.exists());
+}
+
+#[test]
+fn parse_cdn_expiry() {
+    let url = "https://scontent.cdninstagram.com/v/t51.2885-15/1.jpg?stp=dst-jpg&_nc_ht=scontent.cdninstagram.com&oh=00_AYB&oe=66A1B2C3";
+    assert_eq!(cdn::expiry(url), Some(0x66A1B2C3));
+    assert!(cdn::is_expired(url));
+
+    assert_eq!(cdn::expiry("https://example.com/media.jpg"), None);
+    assert!(!cdn::is_expired(
+        "https://example.com/media.jpg?oe=FFFFFFFFFF"
+    ));
+}
+
+#[test]
+fn expire_renditions_and_attachments() {
+    let expired = "https://example.com/320.jpg?oe=66A1B2C3";
+    let fresh = "https://example.com/1080.jpg?oe=FFFFFFFFFF";
+
+    // Only a smaller rendition has expired
+    let post = subpost(json!({
+        "media_type": 1,
+        "image_versions2": {
+            "candidates": [
+                { "url": fresh, "width": 1080, "height": 1350 },
+                { "url": expired, "width": 320, "height": 400 }
+            ]
+        }
+    }))
+    .unwrap();
+    assert!(post.media[0].is_expired());
+    assert_eq!(post.media[0].expires_at(), Some(0x66A1B2C3));
+    assert!(post.is_expired());
+
+    let quoting = subpost(json!({
+        "text_post_app_info": {
+            "share_info": {
+                "quoted_post": {
+                    "code": "C2QBoRaRmR1",
+                    "taken_at": 1705000000,
+                    "user": { "username": "zuck", "profile_pic_url": expired }
+                }
+            }
+        }
+    }))
+    .unwrap();
+    assert!(quoting.is_expired());
+
+    let previewing = subpost(json!({
+        "text_post_app_info": {
+            "link_preview_attachment": { "url": "https://example.com/", "image_url": expired }
+        }
+    }))
+    .unwrap();
+    assert!(previewing.is_expired());
+
+    assert!(!subpost(json!({})).unwrap().is_expired());
+}
+
+#[test]
+fn report_missing_fields() {
+    let no_code = json!({ "post": { "taken_at": 1718000000 } });
+    let err = Threads::build_subpost(&thread_item(no_code), &item_location()).unwrap_err();
+    assert!(matches!(err, SpoolsError::MissingField { .. }));
+    assert_eq!(err.operation(), Some("post_thread"));
+    assert_eq!(
+        err.pointer(),
+        Some("/data/data/edges/0/node/thread_items/0/post/code")
+    );
+
+    let broken_media = json!({
+        "image_versions2": { "candidates": [{ "width": 1080, "height": 1350 }] }
+    });
+    assert_eq!(
+        subpost(broken_media).unwrap_err().pointer(),
+        Some("/data/data/edges/0/node/thread_items/0/post/image_versions2/candidates/0/url")
+    );
+
+    // Carousel items and quoted posts are pointed to within the post
+    let broken_carousel = json!({
+        "carousel_media": [
+            {
+                "image_versions2": {
+                    "candidates": [{ "url": "https://example.com/1.jpg", "width": 1, "height": 1 }]
+                }
+            },
+            {
+                "image_versions2": {
+                    "candidates": [{ "url": "https://example.com/2.jpg", "height": 1 }]
+                }
+            }
+        ]
+    });
+    assert_eq!(
+        subpost(broken_carousel).unwrap_err().pointer(),
+        Some("/data/data/edges/0/node/thread_items/0/post/carousel_media/1/image_versions2/candidates/0/width")
+    );
+
+    let quotes = json!({
+        "data": {
+            "data": {
+                "edges": [
+                    {
+                        "node": {
+                            "thread_items": [{ "post": { "code": "C2QcQuOtE01", "taken_at": 1 } }]
+                        }
+                    },
+                    {
+                        "node": {
+                            "thread_items": [{
+                                "post": {
+                                    "code": "C2QcQuOtE02",
+                                    "taken_at": 1,
+                                    "text_post_app_info": {
+                                        "share_info": { "quoted_post": { "code": "C2QBoRaRmR1" } }
+                                    }
+                                }
+                            }]
+                        }
+                    }
+                ]
+            }
+        }
+    });
+    let err = Threads::build_quotes("C2QBoRaRmR1", quotes).unwrap_err();
+    assert_eq!(err.operation(), Some("post_quotes"));
+    assert_eq!(
+        err.pointer(),
+        Some("/data/data/edges/1/node/thread_items/0/post/text_post_app_info/share_info/quoted_post/taken_at")
+    );
+}
+
+#[test]
+fn report_invalid_field_types() {
+    let quotes = json!({
+        "data": {
+            "data": {
+                "edges": [{
+                    "node": {
+                        "thread_items": [{
+                            "post": {
+                                "code": "C2QcQuOtE01",
+                                "taken_at": 1705100000,
+                                "caption": { "text": 42 }
+                            }
+                        }]
+                    }
+                }]
+            }
+        }
+    });
+
+    let err = Threads::build_quotes("C2QBoRaRmR1", quotes).unwrap_err();
+    assert!(matches!(err, SpoolsError::InvalidField { .. }), "{:?}", err);
+    assert_eq!(err.operation(), Some("post_quotes"));
+    assert_eq!(
+        err.pointer(),
+        Some("/data/data/edges/0/node/thread_items/0/post/caption/text")
+    );
+
+    let reposters = json!({
+        "data": { "reposters": { "users": [{ "username": "zuck", "is_verified": 1.5 }] } }
+    });
+
+    let err = Threads::build_reposters("C2QBoRaRmR1", reposters).unwrap_err();
+    assert_eq!(err.pointer(), Some("/data/reposters/users/0/is_verified"));
+}
+
+#[test]
+fn parse_quoted_post() {
+    let fixture = json!({
+        "user": { "username": "mosseri", "profile_pic_url": null, "is_verified": true },
+        "caption": { "text": "This!" },
+        "like_count": 10,
+        "text_post_app_info": {
+            "direct_reply_count": 2,
+            "share_info": {
+                "quoted_post": {
+                    "code": "C2QBoRaRmR1",
+                    "taken_at": 1705000000,
+                    "user": { "username": "zuck", "is_verified": true },
+                    "caption": { "text": "Original" },
+                    "text_post_app_info": null
+                }
+            }
+        }
+    });
+
+    let post = subpost(fixture).unwrap();
+    println!("{:#?}", post);
+    assert_eq!(post.interactions.likes, 10);
+    assert_eq!(post.interactions.replies, 2);
+
+    match post.quote {
+        Some(Quote::Available(quoted)) => {
+            assert_eq!(quoted.code, "C2QBoRaRmR1");
+            assert_eq!(quoted.author.username, "zuck");
+            assert_eq!(quoted.body, "Original");
+        }
+        other => panic!("expected quoted post, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn fetch_raw_post() {
+    let client = Threads::new().unwrap();
+    let resp = client.fetch_post_raw("C2QBoRaRmR1").await;
+    assert!(resp.is_ok());
+
+    let (post, raw) = resp.unwrap();
+    assert_eq!(post.id, "3283131293873103989");
+    assert!(raw.pointer("/data/data/edges").is_some());
+}
+
+#[test]
+fn classify_meta_errors() {
+    let rate_limited = json!({ "errors": [{ "code": 1675004, "summary": "Rate limit exceeded" }] });
+    let err = SpoolsError::deserialize_error("post_thread", rate_limited);
+    assert!(matches!(err, SpoolsError::RateLimited { .. }));
+    assert!(err.is_retryable());
+    assert_eq!(err.operation(), Some("post_thread"));
+
+    let stale = json!({ "errors": [{ "code": 1675002 }] });
+    let err = SpoolsError::deserialize_error("post_thread", stale);
+    assert!(matches!(err, SpoolsError::StaleDocId { .. }));
+    assert!(!err.is_retryable());
+
+    let transient = json!({ "errors": [{ "code": 2, "summary": "Oops", "is_transient": true }] });
+    let err = SpoolsError::deserialize_error("post_thread", transient);
+    assert_eq!(err.meta_errors().len(), 1);
+    assert_eq!(err.meta_errors()[0].code, Some(2));
+    assert!(err.is_retryable());
+
+    // Meta errors sent along with an unsuccessful status are kept
+    let body = r#"{"errors":[{"code":1675004,"summary":"Rate limit exceeded"}]}"#;
+    let err = SpoolsError::status_with_body("post_thread", StatusCode::BAD_REQUEST, Some(30), body);
+    assert!(matches!(
+        err,
+        SpoolsError::RateLimited {
+            retry_after: Some(30),
+            ..
+        }
+    ));
+
+    let body = r#"{"errors":[{"code":2,"summary":"Oops"}]}"#;
+    let err =
+        SpoolsError::status_with_body("post_thread", StatusCode::INTERNAL_SERVER_ERROR, None, body);
+    assert_eq!(err.meta_errors()[0].code, Some(2));
+
+    let err = SpoolsError::status_with_body("post_thread", StatusCode::BAD_GATEWAY, None, "<html>");
+    assert_eq!(err.http_status(), Some(502));
+    assert!(err.is_retryable());
+}
+
+#[test]
+fn classify_unavailable_objects() {
+    let empty = json!({ "data": { "xdt_user_by_username": null } });
+    let err =
+        SpoolsError::deserialize_error("user_by_username", empty).for_object(Types::User, "ghost");
+    assert!(matches!(
+        err,
+        SpoolsError::NotFound {
+            kind: Types::User,
+            ..
+        }
+    ));
+    assert_eq!(err.id(), Some("ghost"));
+
+    let suspended = json!({ "errors": [{ "summary": "This account has been suspended" }] });
+    let err = SpoolsError::deserialize_error("user_by_username", suspended)
+        .for_object(Types::User, "banned");
+    assert_eq!(err.restriction(), Some(Restriction::Suspended));
+    assert_eq!(err.id(), Some("banned"));
+
+    let page =
+        "<html><head><title>This post is not available in your country</title></head></html>";
+    let err = SpoolsError::not_found(Types::Post, "C8kPz1aBrKn", page);
+    assert_eq!(err.restriction(), Some(Restriction::RegionRestricted));
+    assert_eq!(
+        err.to_string(),
+        "post C8kPz1aBrKn is restricted in this region"
+    );
+
+    // Strings elsewhere in the page don't count
+    let page = r#"<html><head><title>Threads</title></head><script>{"suspended":"Your account was suspended","age":"You must be 18 or older"}</script></html>"#;
+    let err = SpoolsError::not_found(Types::Post, "C8kPz1aBrKn", page);
+    assert!(matches!(err, SpoolsError::NotFound { .. }));
+}
+
+#[test]
+fn build_private_profile() {
+    let private: raw::User = serde_json::from_value(json!({
+        "username": "hidden",
+        "full_name": "Hidden",
+        "follower_count": 12,
+        "text_post_app_is_private": true
+    }))
+    .unwrap();
+
+    // Private profiles are still returned, just without their posts
+    let user = Threads::build_profile("hidden", 314216, &private, &raw::User::default(), vec![]);
+    assert!(user.private);
+    assert_eq!(user.name, "Hidden");
+    assert_eq!(user.followers, 12);
+    assert!(user.posts.is_empty());
+
+    let fallback: raw::User =
+        serde_json::from_value(json!({ "username": "hidden", "is_private": true })).unwrap();
+    let user = Threads::build_profile("hidden", 314216, &fallback, &raw::User::default(), vec![]);
+    assert!(user.private);
+
+    let user = Threads::build_profile(
+        "zuck",
+        314,
+        &raw::User::default(),
+        &raw::User::default(),
+        vec![],
+    );
+    assert!(!user.private);
+}
+
+#[test]
+fn detect_interstitials() {
+    let login = Interstitial::detect(
+        "https://www.threads.net/login?next=%2Fpost%2FC2QBoRaRmR1",
+        "",
+    );
+    assert_eq!(login, Some(Interstitial::Login));
+
+    // Login walls are reported the same way, whether they come as a page, status or Meta error
+    let err = SpoolsError::status("post_thread", StatusCode::UNAUTHORIZED, None);
+    assert!(matches!(
+        err,
+        SpoolsError::Blocked {
+            wall: Interstitial::Login,
+            ..
+        }
+    ));
+
+    let reauth = json!({ "errors": [{ "code": 1357001, "requires_reauth": true }] });
+    let err = SpoolsError::deserialize_error("post_thread", reauth);
+    assert!(matches!(
+        err,
+        SpoolsError::Blocked {
+            wall: Interstitial::Login,
+            ..
+        }
+    ));
+    assert_eq!(err.operation(), Some("post_thread"));
+
+    let checkpoint = Interstitial::detect(
+        "https://www.threads.net/api/graphql",
+        r#"{"message":"checkpoint_required","checkpoint_url":"/challenge/"}"#,
+    );
+    assert_eq!(checkpoint, Some(Interstitial::Checkpoint));
+
+    let post = Interstitial::detect(
+        "https://www.threads.net/post/C2QBoRaRmR1",
+        r#"<meta content="{&quot;post_id&quot;:&quot;3283131293873103989&quot;}">"#,
+    );
+    assert_eq!(post, None);
+}
+
+#[test]
+fn convert_shortcodes() {
+    let code: Shortcode = "C2QBoRaRmR1".parse().unwrap();
+    let id = code.to_id().unwrap();
+    assert_eq!(id.get(), 3283131293873103989);
+    assert_eq!(id.to_shortcode(), code);
+
+    let id: PostId = "3283131293873103989_314216".parse().unwrap();
+    assert_eq!(id.to_shortcode().as_str(), "C2QBoRaRmR1");
+
+    assert!("not a code".parse::<Shortcode>().is_err());
+    assert!(Shortcode::from(PostId::new(0)).to_id().is_ok());
+    assert!("AC2QBoRaRmR1"
+        .parse::<Shortcode>()
+        .unwrap()
+        .to_id()
+        .is_err());
+    assert!("C2QBoRaRmR1C2QBoRaRmR1"
+        .parse::<Shortcode>()
+        .unwrap()
+        .to_id()
+        .is_err());
+}
+
+#[tokio::test]
+async fn fetch_by_numeric_id() {
+    let client = Threads::new().unwrap();
+
+    let post = client.fetch_post_by_id(3283131293873103989).await;
+    assert!(post.is_ok());
+    assert_eq!(post.unwrap().code, "C2QBoRaRmR1");
+
+    let user = client.fetch_user_by_id(314216).await;
+    assert!(user.is_ok());
+
+    let user = user.unwrap();
+    assert_eq!(user.username, "zuck");
+    assert!(user.verified);
+    assert!(user.followers > 0);
+    assert!(!user.pfp.is_empty());
+}
+
+#[test]
+fn parse_references() {
+    let post = ThreadsRef::Post {
+        username: Some("zuck".to_string()),
+        code: "C2QBoRaRmR1".parse().unwrap(),
+    };
+    let user = ThreadsRef::User("zuck".to_string());
+
+    for input in [
+        "https://www.threads.net/@zuck/post/C2QBoRaRmR1",
+        "threads.com/@zuck/post/C2QBoRaRmR1/media?xmt=abc",
+        "http://threads.net/@Zuck/post/C2QBoRaRmR1#replies",
+    ] {
+        assert_eq!(input.parse::<ThreadsRef>().unwrap(), post, "{}", input);
+    }
+
+    for input in [
+        "@zuck",
+        "zuck",
+        "@zuck@threads.net",
+        "https://www.threads.net/@zuck?igshid=NTc4MTIwNjQ2YQ==",
+        "https://www.instagram.com/zuck/",
+    ] {
+        assert_eq!(input.parse::<ThreadsRef>().unwrap(), user, "{}", input);
+    }
+
+    assert_eq!(
+        "https://www.threads.net/post/C2QBoRaRmR1"
+            .parse::<ThreadsRef>()
+            .unwrap()
+            .url(),
+        "https://www.threads.net/post/C2QBoRaRmR1"
+    );
+    assert_eq!(post.url(), "https://www.threads.net/@zuck/post/C2QBoRaRmR1");
+
+    for input in [
+        "https://example.com/@zuck",
+        "threads.net",
+        "https://www.instagram.com/p/C2QBoRaRmR1/",
+        "@zuck@mastodon.social",
+    ] {
+        assert!(input.parse::<ThreadsRef>().is_err(), "{}", input);
+    }
+}
+
+#[test]
+fn build_conversation_tree() {
+    let chain = |codes: &[&str]| {
+        codes
+            .iter()
+            .map(|code| {
+                let fixture = json!({
+                    "code": code,
+                    "user": { "username": format!("user_{}", code.to_lowercase()) },
+                    "text_post_app_info": { "is_reply": *code != "ROOT" }
+                });
+                subpost(fixture).unwrap()
+            })
+            .collect::<Vec<_>>()
+    };
+
+    let mut chains = vec![
+        chain(&["ROOT", "MID", "TARGET", "SELF"]),
+        chain(&["A", "AA"]),
+        chain(&["A", "AB", "ABA"]),
+        chain(&["B"]),
+    ];
+    threads::link_root(chains.iter_mut().flatten());
+
+    let conversation = Conversation::from_chains(&chains, "TARGET", 2).unwrap();
+    let root = conversation.root.as_ref().unwrap();
+    assert_eq!(root.code, "ROOT");
+    assert_eq!(root.root, None);
+    assert_eq!(conversation.post.code, "TARGET");
+    assert_eq!(conversation.post.root.as_deref(), Some("ROOT"));
+    assert_eq!(conversation.replies[2].post.root.as_deref(), Some("ROOT"));
+
+    let ancestors: Vec<&str> = conversation
+        .ancestors
+        .iter()
+        .map(|post| post.code.as_str())
+        .collect();
+    assert_eq!(ancestors, ["ROOT", "MID"]);
+
+    let replies: Vec<&str> = conversation
+        .replies
+        .iter()
+        .map(|reply| reply.post.code.as_str())
+        .collect();
+    assert_eq!(replies, ["SELF", "A", "B"]);
+
+    // Both chains starting with A are merged, and ABA is past the depth limit
+    let branch = &conversation.replies[1];
+    let nested: Vec<&str> = branch
+        .replies
+        .iter()
+        .map(|reply| reply.post.code.as_str())
+        .collect();
+    assert_eq!(nested, ["AA", "AB"]);
+    assert_eq!(branch.count(), 2);
+
+    assert!(Conversation::from_chains(&chains, "MISSING", 2).is_none());
+}
+
+#[test]
+fn build_conversation_without_root() {
+    let reply = |code: &str| {
+        subpost(json!({ "code": code, "text_post_app_info": { "is_reply": true } })).unwrap()
+    };
+
+    // Threads starting partway through a conversation don't include its root
+    let mut chains = vec![vec![reply("MID"), reply("TARGET")], vec![reply("A")]];
+    threads::link_root(chains.iter_mut().flatten());
+
+    let conversation = Conversation::from_chains(&chains, "TARGET", 2).unwrap();
+    assert!(conversation.root.is_none());
+    assert_eq!(conversation.ancestors[0].code, "MID");
+    assert_eq!(conversation.post.root, None);
+
+    let conversation = Conversation::from_chains(&chains, "MID", 2).unwrap();
+    assert!(conversation.root.is_none());
+    assert!(conversation.ancestors.is_empty());
+}
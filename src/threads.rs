@@ -1,13 +1,25 @@
-use std::iter::repeat_with;
+use std::{iter::repeat_with, path::Path};
 
 use crate::{
-    error::{SpoolsError, Types},
-    media::Media,
-    post::{Post, Subpost},
+    attachment::{LinkPreview, Poll},
+    conversation::Conversation,
+    entity::{Entity, EntityKind},
+    error::{Interstitial, Location, SpoolsError, Types},
+    id::{PostId, Shortcode},
+    media::{Media, Rendition},
+    post::{Interactions, Post, Quote, ReplyControl, Subpost},
+    raw::{self, Nodes},
+    reference::{Resolved, ThreadsRef},
     user::{Author, User},
 };
-use reqwest::{header, Client, StatusCode};
-use serde_json::Value;
+use reqwest::{header, Client, Response, StatusCode};
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+use serde_path_to_error::Segment;
+use tokio::{
+    fs::{self, OpenOptions},
+    io::{AsyncWrite, AsyncWriteExt},
+};
 
 /// Threads pseudo-client
 ///
@@ -19,16 +31,96 @@ pub struct Threads {
     client: Client,
 }
 
-// Implement internal trait to ease unwrapping strings
-trait ValueString {
-    fn clean_string(&self) -> String;
+/// GraphQL query, named for errors and identified by Threads through its document ID
+#[derive(Debug, Clone, Copy)]
+struct Query {
+    name: &'static str,
+    doc_id: &'static str,
+}
+
+const USER_BY_USERNAME: Query = Query {
+    name: "user_by_username",
+    doc_id: "7394812507255098",
+};
+const USER_BY_ID: Query = Query {
+    name: "user_by_id",
+    doc_id: "25253062544340717",
+};
+const USER_THREADS: Query = Query {
+    name: "user_threads",
+    doc_id: "7357407954367176",
+};
+const POST_THREAD: Query = Query {
+    name: "post_thread",
+    doc_id: "9021231181224706",
+};
+const POST_QUOTES: Query = Query {
+    name: "post_quotes",
+    doc_id: "7439738349408636",
+};
+const POST_REPOSTERS: Query = Query {
+    name: "post_reposters",
+    doc_id: "7505402879552818",
+};
+
+/// Deserialize the data within a GraphQL response into its response model
+///
+/// Fields with an unexpected type are reported along with their location in the response.
+fn parse<T>(query: Query, resp: &Value) -> Result<T, SpoolsError>
+where
+    T: DeserializeOwned + Default + Nodes,
+{
+    let mut data =
+        serde_path_to_error::deserialize::<_, raw::Response<T>>(resp).map_err(|err| {
+            SpoolsError::InvalidField {
+                operation: query.name.to_string(),
+                pointer: pointer(err.path()),
+                message: err.inner().to_string(),
+            }
+        })?;
+
+    data.keep_nodes(resp);
+
+    Ok(data.data.unwrap_or_default())
+}
+
+/// Convert a path tracked while deserializing into a JSON pointer
+fn pointer(path: &serde_path_to_error::Path) -> String {
+    path.iter()
+        .filter_map(|segment| match segment {
+            Segment::Seq { index } => Some(format!("/{}", index)),
+            Segment::Map { key } => Some(format!("/{}", key.replace('~', "~0").replace('/', "~1"))),
+            Segment::Enum { variant } => Some(format!("/{}", variant)),
+            Segment::Unknown => None,
+        })
+        .collect()
 }
 
-impl ValueString for Value {
-    /// Convert serialized JSON string into clean std::String
-    fn clean_string(&self) -> String {
-        self.as_str().unwrap_or("").to_string()
+/// Get the amount of seconds to wait before retrying out of a response's headers
+fn retry_after(resp: &Response) -> Option<u64> {
+    resp.headers()
+        .get(header::RETRY_AFTER)
+        .and_then(|val| val.to_str().ok())
+        .and_then(|val| val.parse::<u64>().ok())
+}
+
+/// Point every reply within a thread to the post which started it
+///
+/// Threads starting with a reply don't include their root, so they're left alone.
+pub(crate) fn link_root<'a>(thread: impl IntoIterator<Item = &'a mut Subpost>) {
+    let mut posts = thread.into_iter();
+
+    let Some(first) = posts.next() else {
+        return;
+    };
+
+    if first.is_reply {
+        return;
     }
+
+    posts
+        .filter(|post| post.is_reply)
+        .for_each(|post| post.root = Some(first.code.to_owned()));
 }
 
 impl Threads {
@@ -56,7 +148,7 @@ impl Threads {
     }
 
     /// Send a GraphQL query to Threads and return a JSON document
-    async fn query(&self, variables: &str, doc_id: &str) -> Result<Value, SpoolsError> {
+    async fn query(&self, variables: &str, query: Query) -> Result<Value, SpoolsError> {
         // Meta uses 11 characters, though 12 also works
         let lsd: String = repeat_with(fastrand::alphanumeric).take(11).collect();
 
@@ -67,21 +159,21 @@ impl Threads {
                 &format!(
                     "{{{},
 \"__relay_internal__pv__BarcelonaIsLoggedInrelayprovider\":false,
-\"__relay_internal__pv__BarcelonaShouldShowFediverseM1Featuresrelayprovider\":false,
+\"__relay_internal__pv__BarcelonaShouldShowFediverseM1Featuresrelayprovider\":true,
 \"__relay_internal__pv__BarcelonaIsInlineReelsEnabledrelayprovider\":true,
 \"__relay_internal__pv__BarcelonaUseCometVideoPlaybackEnginerelayprovider\":false,
 \"__relay_internal__pv__BarcelonaOptionalCookiesEnabledrelayprovider\":true,
 \"__relay_internal__pv__BarcelonaShouldShowFediverseM075Featuresrelayprovider\":false,
 \"__relay_internal__pv__BarcelonaIsThreadContextHeaderEnabledrelayprovider\":false,
 \"__relay_internal__pv__BarcelonaIsSableEnabledrelayprovider\":false,
-\"__relay_internal__pv__BarcelonaShowReshareCountrelayprovider\":false,
+\"__relay_internal__pv__BarcelonaShowReshareCountrelayprovider\":true,
 \"__relay_internal__pv__BarcelonaIsThreadContextHeaderEnabledrelayprovider\":false,
 \"__relay_internal__pv__BarcelonaIsLinkVerificationEnabledrelayprovider\":false,
             }}",
                     variables
                 ),
             ),
-            ("doc_id", doc_id),
+            ("doc_id", query.doc_id),
         ];
 
         let resp = self
@@ -91,347 +183,907 @@ impl Threads {
             .header("X-FB-LSD", lsd)
             .send()
             .await
-            .map_err(SpoolsError::RequestError)?;
+            .map_err(SpoolsError::request(query.name))?;
+
+        if !resp.status().is_success() {
+            let status = resp.status();
+            let retry = retry_after(&resp);
+            let body = resp.text().await.unwrap_or_default();
+
+            return Err(SpoolsError::status_with_body(
+                query.name, status, retry, &body,
+            ));
+        }
 
-        let deser = resp
-            .json::<Value>()
+        let url = resp.url().to_string();
+        let body = resp
+            .text()
             .await
-            .map_err(|_| SpoolsError::InvalidResponse)?;
+            .map_err(|_| SpoolsError::invalid(query.name))?;
+
+        // Login walls and checkpoints come back as HTML pages, or JSON with only a message
+        let wall = Interstitial::detect(&url, &body);
+        let deser = match serde_json::from_str::<Value>(&body) {
+            Ok(deser) if wall.is_none() || deser.get("data").is_some() => deser,
+            _ => {
+                return Err(SpoolsError::Blocked {
+                    operation: query.name.to_string(),
+                    wall: wall.unwrap_or(Interstitial::Other),
+                })
+            }
+        };
 
         Ok(deser)
     }
 
-    /// Retrieve post ID from shortcode
+    /// Get the numeric ID for a shortcode, looking it up on the post's page if it can't be
+    /// converted locally
+    async fn resolve_post_id(&self, code: &str) -> Result<String, SpoolsError> {
+        match code.parse::<Shortcode>().and_then(|code| code.to_id()) {
+            Ok(id) => Ok(id.to_string()),
+            Err(_) => self.fetch_post_id(code).await,
+        }
+    }
+
+    /// Retrieve post ID from shortcode, through the post's page
     async fn fetch_post_id(&self, code: &str) -> Result<String, SpoolsError> {
+        let url = format!("https://www.threads.net/post/{}", code);
+
         let fetch = self
             .client
-            .get(format!("https://www.threads.net/post/{}", code))
+            .get(&url)
             .header("Sec-Fetch-Node", "navigate")
             .send()
             .await
-            .map_err(SpoolsError::RequestError)?;
-
-        match fetch.error_for_status() {
-            Ok(fetch) => {
-                let resp = fetch
-                    .text()
-                    .await
-                    .map_err(|_| SpoolsError::InvalidResponse)?;
-
-                // Finds the ID, located in a meta tag containing JSON data
-                let id_location = resp.find("post_id");
-
-                if id_location.is_none() {
-                    return Err(SpoolsError::NotFound(Types::Post));
-                }
-
-                // Prepare values to select the ID
-                let mut cur = id_location.unwrap() + 10;
-                let mut curchar = resp.as_bytes()[cur] as char;
-                let mut id = String::new();
-
-                while curchar != '\"' {
-                    id.push(curchar);
-                    cur += 1;
-                    curchar = resp.as_bytes()[cur] as char;
-                }
-
-                return Ok(id);
-            }
-            Err(err) => {
-                return Err(SpoolsError::ResponseError(
-                    err.status()
-                        .unwrap_or(StatusCode::INTERNAL_SERVER_ERROR)
-                        .to_string(),
-                ));
+            .map_err(SpoolsError::request(&url))?;
+
+        let status = fetch.status();
+
+        if status == StatusCode::NOT_FOUND {
+            let page = fetch.text().await.unwrap_or_default();
+            return Err(SpoolsError::not_found(Types::Post, code, &page));
+        }
+
+        if !status.is_success() {
+            return Err(SpoolsError::status(&url, status, retry_after(&fetch)));
+        }
+
+        let location = fetch.url().to_string();
+        let resp = fetch.text().await.map_err(|_| SpoolsError::invalid(&url))?;
+
+        // Meta serves a login or checkpoint page instead of the post when it wants us gone
+        if !resp.contains("post_id") {
+            if let Some(wall) = Interstitial::detect(&location, &resp) {
+                return Err(SpoolsError::Blocked {
+                    operation: url,
+                    wall,
+                });
             }
         }
+
+        // Finds the ID, located in a meta tag containing JSON data
+        let id_location = resp
+            .find("post_id")
+            .ok_or_else(|| SpoolsError::not_found(Types::Post, code, &resp))?;
+
+        // The ID starts after the key's closing quote, colon and opening quote
+        let id = resp
+            .get(id_location + 10..)
+            .and_then(|rest| rest.split_once('"'))
+            .map(|(id, _)| id.to_string())
+            .ok_or_else(|| SpoolsError::invalid(&url))?;
+
+        Ok(id)
+    }
+
+    /// Convert the response model for an embedded user
+    fn build_author(user: &raw::Author) -> Author {
+        Author {
+            username: user.username.to_owned().unwrap_or_default(),
+            pfp: user.profile_pic_url.to_owned().unwrap_or_default(),
+            verified: user.is_verified.unwrap_or(false),
+        }
     }
 
-    /// Deserialize the JSON query for a post
-    fn build_subpost(&self, query: &Value) -> Result<Subpost, SpoolsError> {
-        if let Some(post) = query.pointer("/post") {
-            let code = post
-                .pointer("/code")
-                .unwrap()
-                .as_str()
-                .to_owned()
-                .unwrap()
-                .to_string();
-
-            let author = Author {
-                username: post.pointer("/user/username").unwrap().clean_string(),
-
-                pfp: post
-                    .pointer("/user/profile_pic_url")
-                    .unwrap()
-                    .clean_string(),
-
-                verified: post
-                    .pointer("/user/is_verified")
-                    .unwrap()
-                    .as_bool()
-                    .to_owned()
-                    .unwrap(),
+    /// Convert the fragments for a post's body into entities
+    fn build_entities(body: &str, fragments: &[raw::Fragment]) -> Vec<Entity> {
+        let mut entities: Vec<Entity> = vec![];
+        let mut cursor = 0;
+
+        for fragment in fragments {
+            let text = fragment.plaintext.as_deref().unwrap_or_default();
+
+            // Fragments follow the body's order, so look for each one after the last
+            let start = match body[cursor..].find(text) {
+                Some(offset) if !text.is_empty() => cursor + offset,
+                _ => continue,
             };
+            let range = start..start + text.len();
+            cursor = range.end;
 
-            // Get the post's date
-            let date = post
-                .pointer("/taken_at")
-                .unwrap()
-                .as_u64()
-                .to_owned()
-                .unwrap();
-
-            // Get the post's body
-            let maybe_req = post.pointer("/caption/text");
-
-            let body: String;
-
-            if let Some(maybe_body) = maybe_req {
-                if let Value::String(string) = maybe_body {
-                    body = string.as_str().to_owned().to_string();
-                } else {
-                    return Err(SpoolsError::InvalidResponse);
-                }
-            } else {
-                body = String::new();
-            }
+            let kind = match fragment.fragment_type.as_deref() {
+                Some("mention") => EntityKind::Mention(Self::build_author(
+                    &fragment
+                        .mention_fragment
+                        .to_owned()
+                        .unwrap_or_default()
+                        .mentioned_user,
+                )),
+                Some("link") => EntityKind::Link(
+                    fragment
+                        .link_fragment
+                        .as_ref()
+                        .and_then(|link| link.uri.to_owned())
+                        .unwrap_or_default(),
+                ),
+                Some("tag") => EntityKind::Tag(
+                    fragment
+                        .tag_fragment
+                        .as_ref()
+                        .and_then(|tag| tag.tag_name.to_owned())
+                        .unwrap_or_default(),
+                ),
+                _ => continue,
+            };
 
-            // Checks for array types
-            let image_location = post
-                .pointer("/image_versions2/candidates")
-                .unwrap_or(&Value::Null);
-            let carousel_location = post.pointer("/carousel_media").unwrap_or(&Value::Null);
-
-            // Define media variables
-            let mut media: Vec<Media> = vec![];
-
-            // Check where media could be, if there is any
-            if carousel_location.is_array() {
-                // Carousel media
-                media = carousel_location
-                    .as_array()
-                    .unwrap()
-                    .clone()
-                    .iter_mut()
-                    .map(|node| Media::from(node.clone()).unwrap())
-                    .collect();
-            } else if image_location.is_array()
-                && !image_location.as_array().unwrap_or(&vec![]).is_empty()
-            {
-                // Singular media
-                media.push(Media::from(post.clone())?)
-            }
+            entities.push(Entity { kind, range });
+        }
 
-            Ok(Subpost {
-                code,
-                author,
-                date,
-                body,
-                media,
-                likes: post["like_count"].as_u64().unwrap_or(0),
-            })
+        entities
+    }
+
+    /// Convert the response model for an item within a thread, given where it's located
+    pub(crate) fn build_subpost(
+        item: &raw::ThreadItem,
+        at: &Location,
+    ) -> Result<Subpost, SpoolsError> {
+        if let Some(post) = &item.post {
+            Self::build_post(post, &at.join("post"))
         } else {
-            Err(SpoolsError::InvalidResponse)
+            Err(at.missing("/post"))
+        }
+    }
+
+    /// Convert the response model for a thread into its posts, given where its items are located
+    fn build_thread(items: &[raw::ThreadItem], at: &Location) -> Result<Vec<Subpost>, SpoolsError> {
+        items
+            .iter()
+            .enumerate()
+            .map(|(index, item)| Self::build_subpost(item, &at.join(index)))
+            .collect()
+    }
+
+    /// Convert the response model for a post, given where it's located
+    fn build_post(post: &raw::Post, at: &Location) -> Result<Subpost, SpoolsError> {
+        let info = &post.text_post_app_info;
+
+        let code = post.code.to_owned().ok_or_else(|| at.missing("/code"))?;
+
+        let author = Self::build_author(&post.user);
+
+        // Get the post's date
+        let date = post.taken_at.ok_or_else(|| at.missing("/taken_at"))?;
+
+        // Get the post's body
+        let body = post
+            .caption
+            .as_ref()
+            .and_then(|caption| caption.text.to_owned())
+            .unwrap_or_default();
+
+        // Get mentions, links and tags out of the body's fragments
+        let entities = Self::build_entities(
+            &body,
+            info.text_fragments
+                .as_ref()
+                .map(|fragments| fragments.fragments.as_slice())
+                .unwrap_or_default(),
+        );
+
+        // Define media variables
+        let mut media: Vec<Media> = vec![];
+
+        // Check where media could be, if there is any
+        if let Some(carousel) = &post.carousel_media {
+            // Carousel media
+            media = carousel
+                .iter()
+                .enumerate()
+                .map(|(index, item)| Media::from(item, &at.join("carousel_media").join(index)))
+                .collect::<Result<Vec<Media>, SpoolsError>>()?;
+        } else if Media::is_attachment(&post.media) {
+            // Singular media, which is part of the post itself
+            media.push(Media::from(&post.media, at)?)
         }
+
+        // Get the post being quoted, if any
+        let share_info = info.share_info.to_owned().unwrap_or_default();
+
+        let quote = if let Some(quoted) = &share_info.quoted_post {
+            let at = at.join("text_post_app_info/share_info/quoted_post");
+
+            Some(Quote::Available(Box::new(Self::build_post(quoted, &at)?)))
+        } else if share_info
+            .quoted_attachment_post_unavailable
+            .unwrap_or(false)
+        {
+            Some(Quote::Unavailable)
+        } else {
+            None
+        };
+
+        // Get the link preview card, if any
+        let link_preview = match &info.link_preview_attachment {
+            Some(attachment) => Some(LinkPreview::from(
+                attachment,
+                &at.join("text_post_app_info/link_preview_attachment"),
+            )?),
+            None => None,
+        };
+
+        // Get the poll, if any
+        let poll = match &info.poll_attachment {
+            Some(attachment) => Some(Poll::from(
+                attachment,
+                &at.join("text_post_app_info/poll_attachment"),
+            )?),
+            None => None,
+        };
+
+        // Get the post's engagement counters
+        let interactions = Interactions {
+            likes: post.like_count.unwrap_or(0),
+            replies: info.direct_reply_count.unwrap_or(0),
+            reposts: info.repost_count.unwrap_or(0),
+            quotes: info.quote_count.unwrap_or(0),
+            reshares: info.reshare_count.unwrap_or(0),
+        };
+
+        // Get the author being replied to, if any
+        let reply_to = info.reply_to_author.as_ref().map(Self::build_author);
+
+        // Get who can reply to the post, defaulting to everyone
+        let reply_control = info
+            .reply_control
+            .as_deref()
+            .map(ReplyControl::from)
+            .unwrap_or_default();
+
+        Ok(Subpost {
+            code,
+            author,
+            date,
+            body,
+            entities,
+            media,
+            likes: interactions.likes,
+            interactions,
+            quote,
+            link_preview,
+            poll,
+            federated: info.is_federated.unwrap_or(false),
+            pinned: info
+                .pinned_post_info
+                .as_ref()
+                .and_then(|pinned| pinned.is_pinned_to_profile)
+                .unwrap_or(false),
+            edited: post.caption_is_edited.unwrap_or(false),
+            paid_partnership: post.is_paid_partnership.unwrap_or(false),
+            reply_control,
+            is_reply: reply_to.is_some() || info.is_reply.unwrap_or(false),
+            reply_to,
+            root: None,
+        })
     }
 
     /// Fetch user information
     pub async fn fetch_user(&self, tag: &str) -> Result<User, SpoolsError> {
-        // Executes request to get user info from the username
-        let variables = format!("\"username\":\"{}\"", tag);
+        let (user, _) = self.fetch_user_raw(tag).await?;
+
+        Ok(user)
+    }
+
+    /// Fetch user information, along with the JSON documents it was built from
+    ///
+    /// Since users are built from several queries, the responses are returned in an object under
+    /// the `user`, `details` and `threads` keys, holding the user's profile, their information
+    /// fetched by ID and their posts respectively. Posts aren't fetched for private users, so
+    /// `threads` is `null` for them.
+    pub async fn fetch_user_raw(&self, tag: &str) -> Result<(User, Value), SpoolsError> {
         let cloned = self.clone();
+        let (parent, resp) = cloned.fetch_profile(tag).await?;
 
-        let resp = cloned.query(&variables, "7394812507255098").await?;
+        let id = parent
+            .id
+            .as_deref()
+            .and_then(|id| id.parse::<u64>().ok())
+            .ok_or_else(|| {
+                SpoolsError::missing(USER_BY_USERNAME.name, "/data/xdt_user_by_username/id")
+            })?;
 
-        // Gets tree location for value
-        let parent = resp
-            .pointer("/data/xdt_user_by_username")
-            .unwrap_or(&Value::Null);
+        // Executes request to get additional information through the user ID
+        let id_var = format!("\"userID\":\"{}\"", id);
+        let id_resp = cloned.query(&id_var, USER_BY_ID).await?;
 
-        if let Value::Null = parent {
-            let error = SpoolsError::deserialize_error(resp);
+        let details = parse::<raw::UserByIdData>(USER_BY_ID, &id_resp)?
+            .user
+            .unwrap_or_default();
 
-            return Err(if matches!(error, SpoolsError::InvalidResponse) {
-                SpoolsError::NotFound(Types::User)
-            } else {
-                error
-            });
-        }
+        let (user, post_resp) = self.build_user(tag, id, &parent, &details).await?;
 
-        // Defines empty values
-        let mut name: String = String::new();
-        let mut pfp: String = String::new();
-        let mut bio: String = String::new();
-        let mut links: Vec<String> = vec![];
+        let raw = json!({
+            "user": resp,
+            "details": id_resp,
+            "threads": post_resp,
+        });
 
-        // These variables need to be fetched as str, otherwise they'll be wrapped in explicit quote marks
-        let unquot: Vec<String> = ["id", "full_name", "biography"]
-            .iter()
-            .map(|var| parent[var].clean_string())
-            .collect();
+        Ok((user, raw))
+    }
 
-        // Fetches profile picture
-        let pfp_location = parent
-            .pointer("/hd_profile_pic_versions")
-            .unwrap_or(&Value::Null);
+    /// Fetch user information by their numeric ID
+    ///
+    /// Only the username is taken from the ID query, while the profile itself is read from the
+    /// username query, as with [`Threads::fetch_user`].
+    pub async fn fetch_user_by_id(&self, id: u64) -> Result<User, SpoolsError> {
+        let id_var = format!("\"userID\":\"{}\"", id);
+        let id_resp = self.query(&id_var, USER_BY_ID).await?;
 
-        // Gets the highest quality version of the profile pic
-        if let Value::Array(versions) = &pfp_location {
-            pfp = versions[versions.len() - 1]["url"].clean_string();
-        }
+        let details = match parse::<raw::UserByIdData>(USER_BY_ID, &id_resp)?.user {
+            Some(details) => details,
+            None => {
+                return Err(SpoolsError::deserialize_error(USER_BY_ID.name, id_resp)
+                    .for_object(Types::User, &id.to_string()));
+            }
+        };
 
-        // Sets name and bio values if applicable
-        if !unquot[1].is_empty() {
-            name.clone_from(&unquot[1])
-        }
+        let username = details
+            .username
+            .to_owned()
+            .ok_or_else(|| SpoolsError::missing(USER_BY_ID.name, "/data/user/username"))?;
 
-        if !unquot[2].is_empty() {
-            bio.clone_from(&unquot[2])
-        }
+        let (parent, _) = self.fetch_profile(&username).await?;
+        let (user, _) = self.build_user(&username, id, &parent, &details).await?;
 
-        // Executes request to get additional information through the user ID
-        let id_var = format!("\"userID\":\"{}\"", unquot[0]);
-        let id_resp = cloned.query(&id_var, "25253062544340717").await?;
+        Ok(user)
+    }
 
-        // Gets user's bio links
-        let links_parent = id_resp
-            .pointer("/data/user/bio_links")
-            .unwrap_or(&Value::Null);
+    /// Fetch a user's profile through their username, along with the response it was read from
+    async fn fetch_profile(&self, tag: &str) -> Result<(raw::User, Value), SpoolsError> {
+        let variables = format!("\"username\":\"{}\"", tag);
+        let resp = self.query(&variables, USER_BY_USERNAME).await?;
 
-        if let Value::Array(link_array) = &links_parent {
-            links = link_array
-                .iter()
-                .map(|link| link["url"].clean_string())
-                .collect()
+        match parse::<raw::UserData>(USER_BY_USERNAME, &resp)?.xdt_user_by_username {
+            Some(parent) => Ok((parent, resp)),
+            None => Err(SpoolsError::deserialize_error(USER_BY_USERNAME.name, resp)
+                .for_object(Types::User, tag)),
+        }
+    }
+
+    /// Fetch a user's posts and put together their profile
+    ///
+    /// The response for the user's posts is returned alongside them. Posts from private users
+    /// can't be seen without following them, so they aren't fetched and `null` is returned.
+    async fn build_user(
+        &self,
+        tag: &str,
+        id: u64,
+        parent: &raw::User,
+        details: &raw::User,
+    ) -> Result<(User, Value), SpoolsError> {
+        if Self::is_private(parent) {
+            let user = Self::build_profile(tag, id, parent, details, vec![]);
+
+            return Ok((user, Value::Null));
         }
 
         // Executes a request to get the user's posts
-        let cloned: Threads = self.clone();
-        let post_var = format!("\"userID\":\"{}\"", unquot[0]);
-        let post_resp = cloned.query(&post_var, "7357407954367176").await?;
+        let post_var = format!("\"userID\":\"{}\"", id);
+        let post_resp = self.query(&post_var, USER_THREADS).await?;
 
         // Gets user's posts
-        let edges = post_resp
-            .pointer("/data/mediaData/edges")
-            .unwrap_or(&Value::Null);
+        let edges = parse::<raw::UserThreadsData>(USER_THREADS, &post_resp)?
+            .media_data
+            .unwrap_or_default()
+            .edges;
 
-        let posts: Vec<Subpost> = if let Value::Array(nodes) = &edges {
-            nodes
-                .iter()
-                .flat_map(|node| {
-                    let thread_items = node.pointer("/node/thread_items").unwrap();
-
-                    thread_items
-                        .as_array()
-                        .unwrap()
-                        .iter()
-                        .map(|bit| self.build_subpost(bit).unwrap())
-                })
-                .collect()
+        let mut posts: Vec<Subpost> = vec![];
+
+        for (index, node) in edges.into_iter().enumerate() {
+            let at = Location::new(USER_THREADS.name, "/data/mediaData/edges").join(index);
+            let thread_items = node
+                .node
+                .and_then(|thread| thread.thread_items)
+                .ok_or_else(|| at.missing("/node/thread_items"))?;
+
+            let mut thread = Self::build_thread(&thread_items, &at.join("node/thread_items"))?;
+
+            link_root(&mut thread);
+            posts.append(&mut thread);
+        }
+
+        let user = Self::build_profile(tag, id, parent, details, posts);
+
+        Ok((user, post_resp))
+    }
+
+    /// Whether a user's posts are only visible to their followers
+    fn is_private(parent: &raw::User) -> bool {
+        parent
+            .text_post_app_is_private
+            .or(parent.is_private)
+            .unwrap_or(false)
+    }
+
+    /// Convert the response models for a user's profile
+    ///
+    /// The profile is read from `parent`, while bio links are read from `details`, since Threads
+    /// only returns them through the ID query.
+    pub(crate) fn build_profile(
+        tag: &str,
+        id: u64,
+        parent: &raw::User,
+        details: &raw::User,
+        posts: Vec<Subpost>,
+    ) -> User {
+        // Name and bio are left empty if not applicable
+        let name = parent.full_name.to_owned().unwrap_or_default();
+        let bio = parent.biography.to_owned().unwrap_or_default();
+
+        // Gets the highest quality version of the profile pic, falling back to the regular one
+        let pfp = parent
+            .hd_profile_pic_versions
+            .last()
+            .and_then(|version| version.url.to_owned())
+            .or(parent.profile_pic_url.to_owned())
+            .unwrap_or_default();
+
+        // Gets user's bio links
+        let links: Vec<String> = details
+            .bio_links
+            .iter()
+            .map(|link| link.url.to_owned().unwrap_or_default())
+            .collect();
+
+        // Federated users can be reached through ActivityPub under Threads' domain
+        let username = parent.username.to_owned().unwrap_or(tag.to_string());
+        let fediverse = parent.fediverse_sharing_enabled.unwrap_or(false);
+
+        let activitypub_handle = if fediverse {
+            Some(format!("@{}@threads.net", username))
         } else {
-            vec![]
+            None
         };
 
-        Ok(User {
-            id: unquot[0]
-                .parse::<u64>()
-                .map_err(|_| SpoolsError::InvalidResponse)?,
+        User {
+            id,
+            username,
             name,
             pfp,
             bio,
             links,
-            verified: parent["is_verified"].as_bool().unwrap_or(false),
-            followers: parent["follower_count"].as_u64().unwrap_or(0),
+            verified: parent.is_verified.unwrap_or(false),
+            followers: parent.follower_count.unwrap_or(0),
+            fediverse,
+            activitypub_handle,
+            private: Self::is_private(parent),
             posts,
-        })
+        }
     }
 
     /// Fetch post information
     pub async fn fetch_post(&self, code: &str) -> Result<Post, SpoolsError> {
-        // Since there's no endpoint for getting full IDs out of short ones, fetch it from post URL
-        let inner_code = code.to_owned();
+        let (post, _) = self.fetch_post_raw(code).await?;
+
+        Ok(post)
+    }
+
+    /// Fetch post information, along with the JSON document it was built from
+    pub async fn fetch_post_raw(&self, code: &str) -> Result<(Post, Value), SpoolsError> {
+        let id = self.resolve_post_id(code).await?;
+
+        self.fetch_thread(id, code).await
+    }
+
+    /// Fetch post information by its numeric ID, skipping the shortcode lookup
+    ///
+    /// IDs parsed with [`PostId`] can be passed through [`PostId::get`].
+    pub async fn fetch_post_by_id(&self, id: u64) -> Result<Post, SpoolsError> {
+        let id = PostId::new(id);
+        let code = id.to_shortcode();
+
+        let (post, _) = self.fetch_thread(id.to_string(), code.as_str()).await?;
+
+        Ok(post)
+    }
+
+    /// Fetch the reply chains making up the thread around a post, given its ID
+    async fn fetch_chains(
+        &self,
+        id: &str,
+        code: &str,
+    ) -> Result<(Vec<Vec<Subpost>>, Value), SpoolsError> {
+        let variables = format!("\"postID\":\"{}\"", id);
+        let resp = self.query(&variables, POST_THREAD).await?;
+
+        let Some(content) = parse::<raw::ThreadData>(POST_THREAD, &resp)?.data else {
+            return Err(SpoolsError::deserialize_error(POST_THREAD.name, resp)
+                .for_object(Types::Post, code));
+        };
+
+        // Meta wrapping stuff in arrays -.-
+        let mut chains: Vec<Vec<Subpost>> = vec![];
+
+        for (index, node) in content.edges.into_iter().enumerate() {
+            if let Some(thread_items) = node.node.and_then(|thread| thread.thread_items) {
+                let at = Location::new(POST_THREAD.name, "/data/data/edges")
+                    .join(index)
+                    .join("node/thread_items");
+
+                chains.push(Self::build_thread(&thread_items, &at)?);
+            }
+        }
+
+        // The conversation is returned top-down, so its first post is the root
+        link_root(chains.iter_mut().flatten());
+
+        Ok((chains, resp))
+    }
+
+    /// Fetch the conversation around a post as a tree, keeping replies up to `depth` levels deep
+    pub async fn fetch_conversation(
+        &self,
+        code: &str,
+        depth: usize,
+    ) -> Result<Conversation, SpoolsError> {
+        let id = self.resolve_post_id(code).await?;
+        let (chains, _) = self.fetch_chains(&id, code).await?;
+
+        Conversation::from_chains(&chains, code, depth).ok_or_else(|| SpoolsError::NotFound {
+            kind: Types::Post,
+            id: code.to_string(),
+        })
+    }
+
+    /// Fetch the thread around a post, given both of its identifiers
+    async fn fetch_thread(&self, id: String, code: &str) -> Result<(Post, Value), SpoolsError> {
+        let (chains, resp) = self.fetch_chains(&id, code).await?;
+        let subposts: Vec<Subpost> = chains.into_iter().flatten().collect();
+
+        let Some(out) = subposts.iter().find(|post| post.code == code) else {
+            return Err(SpoolsError::NotFound {
+                kind: Types::Post,
+                id: code.to_string(),
+            });
+        };
+
+        let slices: Vec<_> = subposts
+            .split(|out| out.code == code)
+            .collect::<Vec<&[Subpost]>>();
+
+        let parents = match slices.first() {
+            Some(val) => val.to_vec(),
+            None => vec![],
+        };
+
+        let replies = match slices.iter().last() {
+            Some(val) => val
+                .iter()
+                .filter(|val| {
+                    val.reply_to
+                        .as_ref()
+                        .is_some_and(|to| to.username == out.author.username)
+                })
+                .cloned()
+                .collect(),
+            None => vec![],
+        };
+
+        let post = Post {
+            id,
+            code: out.code.to_owned(),
+            author: out.author.to_owned(),
+            date: out.date,
+            body: out.body.to_owned(),
+            entities: out.entities.to_owned(),
+            media: out.media.to_owned(),
+            likes: out.likes,
+            interactions: out.interactions.to_owned(),
+            quote: out.quote.to_owned(),
+            link_preview: out.link_preview.to_owned(),
+            poll: out.poll.to_owned(),
+            federated: out.federated,
+            pinned: out.pinned,
+            edited: out.edited,
+            paid_partnership: out.paid_partnership,
+            reply_control: out.reply_control.to_owned(),
+            is_reply: out.is_reply,
+            reply_to: out.reply_to.to_owned(),
+            root: out.root.to_owned(),
+            parents,
+            replies,
+        };
+
+        Ok((post, resp))
+    }
+
+    /// Fetch the user or post a URL or handle points to
+    pub async fn resolve(&self, input: &str) -> Result<Resolved, SpoolsError> {
+        match input.parse::<ThreadsRef>()? {
+            ThreadsRef::User(username) => {
+                Ok(Resolved::User(Box::new(self.fetch_user(&username).await?)))
+            }
+            ThreadsRef::Post { code, .. } => Ok(Resolved::Post(Box::new(
+                self.fetch_post(code.as_str()).await?,
+            ))),
+        }
+    }
+
+    /// Fetch posts quoting a post
+    pub async fn fetch_quotes(&self, code: &str) -> Result<Vec<Subpost>, SpoolsError> {
         let cloned = self.clone();
-        let id = cloned.fetch_post_id(inner_code.as_str()).await?;
+        let id = cloned.resolve_post_id(code).await?;
 
-        // Now we can fetch the actual post
         let variables = format!("\"postID\":\"{}\"", &id);
-        let resp = cloned.query(&variables, "9021231181224706").await?;
-
-        let check = resp.pointer("/data/data/edges");
-        let post: Post;
-
-        if let Some(Value::Array(content)) = check {
-            // Meta wrapping stuff in arrays -.-
-            let subposts: Vec<(Subpost, String)> = content
-                .clone()
-                .iter_mut()
-                .flat_map(|node| {
-                    if let Value::Array(thread_items) =
-                        &node.pointer("/node/thread_items").unwrap_or(&Value::Null)
-                    {
-                        thread_items
-                            .to_owned()
-                            .iter()
-                            .map(|post| {
-                                let builder = Threads::new().unwrap();
-
-                                let result = builder
-                                    .build_subpost(post)
-                                    .map_err(|_| SpoolsError::SubpostError)
-                                    .unwrap();
-
-                                let rel = post
-                                    .pointer("/post/text_post_app_info/reply_to_author/username")
-                                    .unwrap_or(&Value::Null)
-                                    .clean_string();
-
-                                (result, rel)
-                            })
-                            .collect::<Vec<(Subpost, String)>>()
-                    } else {
-                        vec![]
-                    }
+        let resp = cloned.query(&variables, POST_QUOTES).await?;
+
+        Self::build_quotes(code, resp)
+    }
+
+    /// Convert the response for a post's quotes
+    pub(crate) fn build_quotes(code: &str, resp: Value) -> Result<Vec<Subpost>, SpoolsError> {
+        let check = parse::<raw::ThreadData>(POST_QUOTES, &resp)?.data;
+
+        if let Some(content) = check {
+            // Every quote is wrapped in its own thread, so only its first item is relevant
+            let quotes: Vec<Subpost> = content
+                .edges
+                .iter()
+                .enumerate()
+                .filter_map(|(index, node)| {
+                    let post = node
+                        .node
+                        .as_ref()?
+                        .thread_items
+                        .as_ref()?
+                        .first()?
+                        .post
+                        .as_ref()?;
+                    let at = Location::new(POST_QUOTES.name, "/data/data/edges")
+                        .join(index)
+                        .join("node/thread_items/0/post");
+
+                    Some(Self::build_post(post, &at))
                 })
-                .collect();
-
-            if let Some(out) = subposts.iter().find(|post| post.0.code == code) {
-                let slices: Vec<_> = subposts
-                    .split(|out| out.0.code == code)
-                    .collect::<Vec<&[(Subpost, String)]>>();
-
-                let parents = match slices.first() {
-                    Some(val) => val.iter().map(|post| post.clone().0).collect(),
-                    None => vec![],
-                };
-
-                let replies = match slices.iter().last() {
-                    Some(val) => val
-                        .iter()
-                        .filter(|val| val.1 == out.0.author.username)
-                        .map(|post| post.clone().0)
-                        .collect(),
-                    None => vec![],
-                };
-
-                post = Post {
-                    id,
-                    author: out.0.author.to_owned(),
-                    date: out.0.date,
-                    body: out.0.body.to_owned(),
-                    media: out.0.media.to_owned(),
-                    likes: out.0.likes.to_owned(),
-                    parents,
-                    replies,
-                }
+                .collect::<Result<Vec<Subpost>, SpoolsError>>()?;
+
+            Ok(quotes)
+        } else {
+            Err(SpoolsError::deserialize_error(POST_QUOTES.name, resp)
+                .for_object(Types::Post, code))
+        }
+    }
+
+    /// Fetch users who reposted a post
+    pub async fn fetch_reposters(&self, code: &str) -> Result<Vec<Author>, SpoolsError> {
+        let cloned = self.clone();
+        let id = cloned.resolve_post_id(code).await?;
+
+        let variables = format!("\"media_id\":\"{}\"", &id);
+        let resp = cloned.query(&variables, POST_REPOSTERS).await?;
+
+        Self::build_reposters(code, resp)
+    }
+
+    /// Convert the response for the users who reposted a post
+    pub(crate) fn build_reposters(code: &str, resp: Value) -> Result<Vec<Author>, SpoolsError> {
+        let check = parse::<raw::RepostersData>(POST_REPOSTERS, &resp)?.reposters;
+
+        if let Some(reposters) = check {
+            Ok(reposters.users.iter().map(Self::build_author).collect())
+        } else {
+            Err(SpoolsError::deserialize_error(POST_REPOSTERS.name, resp)
+                .for_object(Types::Post, code))
+        }
+    }
+
+    /// Check whether a post is shared to the fediverse
+    pub async fn is_federated(&self, code: &str) -> Result<bool, SpoolsError> {
+        let post = self.fetch_post(code).await?;
+
+        Ok(post.federated)
+    }
+
+    /// Download a piece of media into a writer, returning the amount of bytes written
+    pub async fn download<W>(&self, media: &Media, writer: &mut W) -> Result<u64, SpoolsError>
+    where
+        W: AsyncWrite + Unpin,
+    {
+        self.download_url(&media.content, writer, 0).await
+    }
+
+    /// Download a specific rendition of a piece of media into a writer
+    pub async fn download_rendition<W>(
+        &self,
+        rendition: &Rendition,
+        writer: &mut W,
+    ) -> Result<u64, SpoolsError>
+    where
+        W: AsyncWrite + Unpin,
+    {
+        self.download_url(&rendition.url, writer, 0).await
+    }
+
+    /// Download a piece of media into a file, resuming from where a previous download left off
+    pub async fn download_to_file(
+        &self,
+        media: &Media,
+        path: impl AsRef<Path>,
+    ) -> Result<u64, SpoolsError> {
+        self.download_url_to_file(&media.content, path).await
+    }
+
+    /// Download a specific rendition of a piece of media into a file, resuming if possible
+    pub async fn download_rendition_to_file(
+        &self,
+        rendition: &Rendition,
+        path: impl AsRef<Path>,
+    ) -> Result<u64, SpoolsError> {
+        self.download_url_to_file(&rendition.url, path).await
+    }
+
+    /// Download a media URL into a file, resuming from where a previous download left off
+    ///
+    /// If the file didn't exist beforehand, it's removed again when the download fails.
+    pub async fn download_url_to_file(
+        &self,
+        url: &str,
+        path: impl AsRef<Path>,
+    ) -> Result<u64, SpoolsError> {
+        let path = path.as_ref();
+        let existed = fs::try_exists(path).await.unwrap_or(true);
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .await
+            .map_err(SpoolsError::WriteError)?;
+
+        let offset = file
+            .metadata()
+            .await
+            .map_err(SpoolsError::WriteError)?
+            .len();
+
+        let written = self.download_url(url, &mut file, offset).await;
+
+        if written.is_err() && !existed {
+            drop(file);
+            let _ = fs::remove_file(path).await;
+        }
+
+        written
+    }
+
+    /// Stream a media URL into a writer, skipping the first `offset` bytes
+    ///
+    /// Resuming is requested through a range header. If the CDN ignores it and sends the whole
+    /// file again, the bytes already written are skipped over instead.
+    pub async fn download_url<W>(
+        &self,
+        url: &str,
+        writer: &mut W,
+        offset: u64,
+    ) -> Result<u64, SpoolsError>
+    where
+        W: AsyncWrite + Unpin,
+    {
+        let mut request = self.client.get(url);
+
+        if offset > 0 {
+            request = request.header(header::RANGE, format!("bytes={}-", offset));
+        }
+
+        let fetch = request.send().await.map_err(SpoolsError::request(url))?;
+
+        // A range past the end of the file means there's nothing left to download
+        if offset > 0 && fetch.status() == StatusCode::RANGE_NOT_SATISFIABLE {
+            return Ok(0);
+        }
+
+        if !fetch.status().is_success() {
+            return Err(SpoolsError::status(
+                url,
+                fetch.status(),
+                retry_after(&fetch),
+            ));
+        }
+
+        let mut resp = fetch;
+
+        // Refuse anything which isn't media, such as error pages served with a success status
+        let content_type = resp
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|val| val.to_str().ok())
+            .unwrap_or_default()
+            .to_owned();
+
+        if !["image/", "video/", "audio/", "application/octet-stream"]
+            .iter()
+            .any(|prefix| content_type.starts_with(prefix))
+        {
+            return Err(SpoolsError::ContentTypeError(content_type));
+        }
+
+        let mut skip = if resp.status() == StatusCode::PARTIAL_CONTENT {
+            0
+        } else {
+            offset
+        };
+        let expected = resp.content_length().map(|len| len.saturating_sub(skip));
+        let mut received: u64 = 0;
+
+        while let Some(chunk) = resp.chunk().await.map_err(SpoolsError::request(url))? {
+            let chunk = if skip > 0 {
+                let skipped = skip.min(chunk.len() as u64);
+                skip -= skipped;
+                chunk.slice(skipped as usize..)
             } else {
-                return Err(SpoolsError::NotFound(Types::Post));
+                chunk
+            };
+
+            writer
+                .write_all(&chunk)
+                .await
+                .map_err(SpoolsError::WriteError)?;
+            received += chunk.len() as u64;
+        }
+
+        writer.flush().await.map_err(SpoolsError::WriteError)?;
+
+        match expected {
+            Some(expected) if expected != received => {
+                Err(SpoolsError::IncompleteDownload { expected, received })
             }
-        } else {
-            return Err(SpoolsError::deserialize_error(resp));
+            _ => Ok(received),
         }
+    }
 
-        Ok(post)
+    /// Re-fetch a post if any of its CDN URLs have expired, returning whether it was refreshed
+    pub async fn refresh_post(&self, post: &mut Post) -> Result<bool, SpoolsError> {
+        if !post.is_expired() {
+            return Ok(false);
+        }
+
+        *post = self.fetch_post(&post.code).await?;
+
+        Ok(true)
+    }
+
+    /// Re-fetch an embedded post's media and author if any of their CDN URLs have expired
+    pub async fn refresh_subpost(&self, post: &mut Subpost) -> Result<bool, SpoolsError> {
+        if !post.is_expired() {
+            return Ok(false);
+        }
+
+        let fresh = self.fetch_post(&post.code).await?;
+        post.author = fresh.author;
+        post.media = fresh.media;
+        post.quote = fresh.quote;
+        post.link_preview = fresh.link_preview;
+
+        Ok(true)
+    }
+
+    /// Re-fetch a user if their profile picture or any of their posts' URLs have expired
+    pub async fn refresh_user(&self, user: &mut User) -> Result<bool, SpoolsError> {
+        if !user.is_expired() {
+            return Ok(false);
+        }
+
+        *user = self.fetch_user(&user.username).await?;
+
+        Ok(true)
     }
 }
@@ -1,16 +1,21 @@
-use crate::{error::SpoolsError, post::Subpost, Threads};
+use crate::{cdn, error::SpoolsError, post::Subpost, reference, Threads};
 use serde::{Deserialize, Serialize};
 
 /// User information and statistics
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct User {
     pub id: u64,
+    pub username: String,
     pub name: String,
     pub pfp: String,
     pub verified: bool,
     pub bio: String,
     pub followers: u64,
     pub links: Vec<String>,
+    pub fediverse: bool,
+    pub activitypub_handle: Option<String>,
+    /// Whether the user's posts are only visible to their followers, leaving `posts` empty
+    pub private: bool,
     pub posts: Vec<Subpost>,
 }
 
@@ -22,12 +27,43 @@ pub struct Author {
     pub verified: bool,
 }
 
+impl User {
+    /// Canonical URL for the user's profile
+    pub fn url(&self) -> String {
+        reference::user_url(&self.username)
+    }
+
+    /// Time at which the profile picture's URL expires, as a UNIX timestamp
+    pub fn pfp_expires_at(&self) -> Option<u64> {
+        cdn::expiry(&self.pfp)
+    }
+
+    /// Whether the profile picture or any of the posts' URLs have expired
+    pub fn is_expired(&self) -> bool {
+        cdn::is_expired(&self.pfp) || self.posts.iter().any(|post| post.is_expired())
+    }
+}
+
 impl Author {
-    /// Convert author into its detailed counterpart
+    /// Canonical URL for the author's profile
+    pub fn url(&self) -> String {
+        reference::user_url(&self.username)
+    }
+
+    /// Time at which the profile picture's URL expires, as a UNIX timestamp
+    pub fn pfp_expires_at(&self) -> Option<u64> {
+        cdn::expiry(&self.pfp)
+    }
+
+    /// Convert author into its detailed counterpart, through a new client
     pub async fn to_user(&self) -> Result<User, SpoolsError> {
         let client = Threads::new()?;
-        let user = client.fetch_user(&self.username).await?;
 
-        Ok(user)
+        self.to_user_with(&client).await
+    }
+
+    /// Convert author into its detailed counterpart, through an existing client
+    pub async fn to_user_with(&self, client: &Threads) -> Result<User, SpoolsError> {
+        client.fetch_user(&self.username).await
     }
 }